@@ -0,0 +1,549 @@
+use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        Configuration,
+    },
+    error::BytecodeError,
+    instruction::Instruction,
+    interpreter::Interpreter,
+    span::{
+        Location,
+        Span,
+    },
+};
+use std::convert::TryFrom;
+
+const MAGIC: &[u8; 4] = b"BFBC";
+const VERSION: u16 = 2;
+const HEADER_SIZE: usize = 24;
+
+// All the binary formats of the crate (i.e., the bytecode, the snapshots and
+// the traces) start with a four byte magic and a little endian version, and
+// encode their integers as LEB128 (and zigzag if signed) unless stated
+// otherwise. The rest of the header of the bytecode is the parts of the
+// configuration, which affect the loaded program, the hash of the script and
+// the checksum of the payload. The payload is the number of instructions
+// followed by the instructions, each of which is an opcode followed by its
+// operands and its span in the script.
+impl Interpreter {
+    pub fn save_bytecode(&self, script: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::with_capacity(self.program().len() * 2);
+        write_unsigned(&mut payload, self.program().len());
+        for (instruction, span) in self.program().iter().zip(self.spans()) {
+            encode(&mut payload, instruction);
+            write_unsigned(&mut payload, span.start.line);
+            write_unsigned(&mut payload, span.start.column);
+            write_unsigned(&mut payload, span.end.line);
+            write_unsigned(&mut payload, span.end.column);
+        }
+
+        let mut bytecode = Vec::with_capacity(HEADER_SIZE + payload.len());
+        bytecode.extend_from_slice(MAGIC);
+        bytecode.extend_from_slice(&VERSION.to_le_bytes());
+        bytecode.push(flags(self.configuration()));
+        bytecode.push(arithmetic(self.configuration()));
+        bytecode.extend_from_slice(&hash(script).to_le_bytes());
+        bytecode.extend_from_slice(&hash(&payload).to_le_bytes());
+        bytecode.extend_from_slice(&payload);
+        bytecode
+    }
+
+    pub fn load_bytecode(&mut self, bytecode: &[u8], script: &[u8]) -> Result<(), BytecodeError> {
+        if bytecode.len() < MAGIC.len() || &bytecode[..MAGIC.len()] != MAGIC {
+            return Err(BytecodeError::InvalidMagic);
+        }
+        if bytecode.len() < HEADER_SIZE {
+            return Err(BytecodeError::Malformed);
+        }
+
+        let version = u16::from_le_bytes([bytecode[4], bytecode[5]]);
+        if version != VERSION {
+            return Err(BytecodeError::UnsupportedVersion(version));
+        }
+
+        let mut source_hash = [0; 8];
+        source_hash.copy_from_slice(&bytecode[8..16]);
+        let mut checksum = [0; 8];
+        checksum.copy_from_slice(&bytecode[16..24]);
+
+        let payload = &bytecode[HEADER_SIZE..];
+        if u64::from_le_bytes(checksum) != hash(payload) {
+            return Err(BytecodeError::ChecksumMismatch);
+        }
+        if u64::from_le_bytes(source_hash) != hash(script) {
+            return Err(BytecodeError::SourceMismatch);
+        }
+        if bytecode[6] != flags(self.configuration()) ||
+            bytecode[7] != arithmetic(self.configuration())
+        {
+            return Err(BytecodeError::ConfigurationMismatch);
+        }
+
+        let mut reader = Reader {
+            payload,
+            position: 0,
+        };
+        let length = reader.unsigned()?;
+        let mut program = Vec::with_capacity(length.min(payload.len()));
+        let mut spans = Vec::with_capacity(length.min(payload.len()));
+        for _ in 0..length {
+            program.push(reader.instruction()?);
+            spans.push(reader.span()?);
+        }
+        let producible = program
+            .iter()
+            .all(|instruction| is_producible(instruction, self.configuration()));
+        if reader.position != payload.len() || !loops_are_linked(&program) || !producible {
+            return Err(BytecodeError::Malformed);
+        }
+
+        let (_, embedded_input) = self.split_script(script);
+        self.set_program(program, spans, embedded_input.map(<[u8]>::to_vec));
+        Ok(())
+    }
+}
+
+fn flags(configuration: &Configuration) -> u8 {
+    configuration.optimize as u8 | (configuration.extensions as u8) << 1
+}
+
+pub(crate) fn arithmetic(configuration: &Configuration) -> u8 {
+    match configuration.arithmetic {
+        ArithmeticPolicy::Wrap => 0,
+        ArithmeticPolicy::Saturate => 1,
+        ArithmeticPolicy::Trap => 2,
+    }
+}
+
+pub(crate) fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+pub(crate) fn encode(payload: &mut Vec<u8>, instruction: &Instruction) {
+    match *instruction {
+        Instruction::MoveLeft(amount) => {
+            payload.push(0);
+            write_unsigned(payload, amount);
+        },
+        Instruction::MoveRight(amount) => {
+            payload.push(1);
+            write_unsigned(payload, amount);
+        },
+        Instruction::Increment(amount) => {
+            payload.push(2);
+            write_unsigned(payload, amount);
+        },
+        Instruction::Decrement(amount) => {
+            payload.push(3);
+            write_unsigned(payload, amount);
+        },
+        Instruction::Read => {
+            payload.push(4);
+        },
+        Instruction::Write => {
+            payload.push(5);
+        },
+        Instruction::StartLoop(end_of_loop) => {
+            payload.push(6);
+            write_unsigned(payload, end_of_loop);
+        },
+        Instruction::EndLoop(start_of_loop) => {
+            payload.push(7);
+            write_unsigned(payload, start_of_loop);
+        },
+        Instruction::SetZero => {
+            payload.push(8);
+        },
+        Instruction::ScanLeft(amount) => {
+            payload.push(9);
+            write_unsigned(payload, amount);
+        },
+        Instruction::ScanRight(amount) => {
+            payload.push(10);
+            write_unsigned(payload, amount);
+        },
+        Instruction::MultiplyAdd(ref targets) => {
+            payload.push(11);
+            write_unsigned(payload, targets.len());
+            for &(offset, factor) in targets {
+                write_signed(payload, offset);
+                write_signed(payload, factor);
+            }
+        },
+        Instruction::IncrementAt(offset, amount) => {
+            payload.push(12);
+            write_signed(payload, offset);
+            write_unsigned(payload, amount);
+        },
+        Instruction::DecrementAt(offset, amount) => {
+            payload.push(13);
+            write_signed(payload, offset);
+            write_unsigned(payload, amount);
+        },
+        Instruction::ReadAt(offset) => {
+            payload.push(14);
+            write_signed(payload, offset);
+        },
+        Instruction::WriteAt(offset) => {
+            payload.push(15);
+            write_signed(payload, offset);
+        },
+        Instruction::Dump => {
+            payload.push(16);
+        },
+    }
+}
+
+pub(crate) fn write_unsigned(payload: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        payload.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    payload.push(value as u8);
+}
+
+pub(crate) fn write_signed(payload: &mut Vec<u8>, value: isize) {
+    write_unsigned(
+        payload,
+        ((value << 1) ^ (value >> (isize::BITS - 1))) as usize,
+    );
+}
+
+// Only the instructions the loader produces with the configuration are
+// accepted, as the others may behave differently than the script or not at all
+// (e.g., a scan by zero cells never ends).
+fn is_producible(instruction: &Instruction, configuration: &Configuration) -> bool {
+    let optimize = configuration.optimize;
+    match *instruction {
+        Instruction::MoveLeft(amount) |
+        Instruction::MoveRight(amount) |
+        Instruction::Increment(amount) |
+        Instruction::Decrement(amount) => amount != 0,
+        Instruction::Read |
+        Instruction::Write |
+        Instruction::StartLoop(_) |
+        Instruction::EndLoop(_) => true,
+        Instruction::SetZero => optimize,
+        Instruction::ScanLeft(amount) | Instruction::ScanRight(amount) => optimize && amount != 0,
+        Instruction::MultiplyAdd(ref targets) => {
+            optimize &&
+                configuration.arithmetic == ArithmeticPolicy::Wrap &&
+                !targets.is_empty() &&
+                targets.iter().all(|&(offset, _)| offset != 0)
+        },
+        Instruction::IncrementAt(offset, amount) | Instruction::DecrementAt(offset, amount) => {
+            optimize && offset != 0 && amount != 0
+        },
+        Instruction::ReadAt(offset) | Instruction::WriteAt(offset) => optimize && offset != 0,
+        Instruction::Dump => configuration.extensions,
+    }
+}
+
+fn loops_are_linked(program: &[Instruction]) -> bool {
+    let mut loop_balancer = Vec::new();
+    for (index, instruction) in program.iter().enumerate() {
+        match *instruction {
+            Instruction::StartLoop(end_of_loop) => {
+                if end_of_loop <= index || end_of_loop >= program.len() {
+                    return false;
+                }
+                loop_balancer.push(index);
+            },
+            Instruction::EndLoop(start_of_loop) => {
+                let linked = loop_balancer.pop() == Some(start_of_loop) &&
+                    program[start_of_loop] == Instruction::StartLoop(index);
+                if !linked {
+                    return false;
+                }
+            },
+            _ => {},
+        }
+    }
+    loop_balancer.is_empty()
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) payload: &'a [u8],
+    pub(crate) position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn byte(&mut self) -> Result<u8, BytecodeError> {
+        let byte = *self
+            .payload
+            .get(self.position)
+            .ok_or(BytecodeError::Malformed)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    pub(crate) fn bytes(&mut self, length: usize) -> Result<&'a [u8], BytecodeError> {
+        let end = self
+            .position
+            .checked_add(length)
+            .ok_or(BytecodeError::Malformed)?;
+        let bytes = self
+            .payload
+            .get(self.position..end)
+            .ok_or(BytecodeError::Malformed)?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn raw_unsigned(&mut self) -> Result<usize, BytecodeError> {
+        let mut value = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let bits = usize::from(byte & 0x7f);
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(BytecodeError::Malformed);
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    // Amounts and indices never exceed isize::MAX in a loaded program, so larger
+    // values can only come from corrupted bytecode.
+    pub(crate) fn unsigned(&mut self) -> Result<usize, BytecodeError> {
+        let value = self.raw_unsigned()?;
+        isize::try_from(value).map_err(|_| BytecodeError::Malformed)?;
+        Ok(value)
+    }
+
+    pub(crate) fn signed(&mut self) -> Result<isize, BytecodeError> {
+        let value = self.raw_unsigned()?;
+        Ok(((value >> 1) as isize) ^ -((value & 1) as isize))
+    }
+
+    fn span(&mut self) -> Result<Span, BytecodeError> {
+        let start = Location::new(self.unsigned()?, self.unsigned()?);
+        let end = Location::new(self.unsigned()?, self.unsigned()?);
+        Ok(Span::new(start, end))
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, BytecodeError> {
+        let instruction = match self.byte()? {
+            0 => Instruction::MoveLeft(self.unsigned()?),
+            1 => Instruction::MoveRight(self.unsigned()?),
+            2 => Instruction::Increment(self.unsigned()?),
+            3 => Instruction::Decrement(self.unsigned()?),
+            4 => Instruction::Read,
+            5 => Instruction::Write,
+            6 => Instruction::StartLoop(self.unsigned()?),
+            7 => Instruction::EndLoop(self.unsigned()?),
+            8 => Instruction::SetZero,
+            9 => Instruction::ScanLeft(self.unsigned()?),
+            10 => Instruction::ScanRight(self.unsigned()?),
+            11 => {
+                let length = self.unsigned()?;
+                let mut targets = Vec::with_capacity(length.min(self.payload.len()));
+                for _ in 0..length {
+                    targets.push((self.signed()?, self.signed()?));
+                }
+                Instruction::MultiplyAdd(targets)
+            },
+            12 => Instruction::IncrementAt(self.signed()?, self.unsigned()?),
+            13 => Instruction::DecrementAt(self.signed()?, self.unsigned()?),
+            14 => Instruction::ReadAt(self.signed()?),
+            15 => Instruction::WriteAt(self.signed()?),
+            16 => Instruction::Dump,
+            _ => return Err(BytecodeError::Malformed),
+        };
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod load_bytecode {
+            use crate::{
+                bytecode::{
+                    HEADER_SIZE,
+                    encode,
+                    hash,
+                    write_unsigned,
+                },
+                configuration::{
+                    ArithmeticPolicy,
+                    Configuration,
+                },
+                error::BytecodeError,
+                instruction::Instruction,
+                interpreter::Interpreter,
+            };
+
+            fn save(configuration: Configuration, script: &[u8]) -> (Interpreter, Vec<u8>) {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                let bytecode = interpreter.save_bytecode(script);
+                (interpreter, bytecode)
+            }
+
+            #[test]
+            fn round_trip() {
+                let scripts: &[&[u8]] = &[
+                    include_bytes!("../assets/99-bottles-of-beer.bf"),
+                    include_bytes!("../assets/cat.bf"),
+                    include_bytes!("../assets/hello-world.bf"),
+                    include_bytes!("../assets/rot13.bf"),
+                    include_bytes!("../assets/sierpinski-triangle.bf"),
+                    b"",
+                    b"+[->>+<<<+>]<<,>.[>]",
+                    b"+>#<[-#]!input",
+                ];
+                for &optimize in &[false, true] {
+                    for &extensions in &[false, true] {
+                        for script in scripts {
+                            let configuration = Configuration {
+                                optimize,
+                                extensions,
+                                ..Configuration::default()
+                            };
+                            let (expected, bytecode) = save(configuration.clone(), script);
+
+                            let mut actual = Interpreter::with_configuration(configuration);
+                            actual.load_bytecode(&bytecode, script).unwrap();
+                            assert_eq!(actual, expected);
+                        }
+                    }
+                }
+            }
+
+            #[test]
+            fn invalid_magic() {
+                let mut interpreter = Interpreter::new();
+                match interpreter.load_bytecode(b"+[-]", b"+[-]") {
+                    Err(BytecodeError::InvalidMagic) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn unsupported_version() {
+                let (mut interpreter, mut bytecode) = save(Configuration::default(), b"+");
+                bytecode[4] = 3;
+                match interpreter.load_bytecode(&bytecode, b"+") {
+                    Err(BytecodeError::UnsupportedVersion(3)) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn checksum_mismatch() {
+                let (mut interpreter, mut bytecode) = save(Configuration::default(), b"+++");
+                *bytecode.last_mut().unwrap() = 4;
+                match interpreter.load_bytecode(&bytecode, b"+++") {
+                    Err(BytecodeError::ChecksumMismatch) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn source_mismatch() {
+                let (mut interpreter, bytecode) = save(Configuration::default(), b"+++");
+                match interpreter.load_bytecode(&bytecode, b"++++") {
+                    Err(BytecodeError::SourceMismatch) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn configuration_mismatch() {
+                let (_, bytecode) = save(Configuration::default(), b"+-");
+                for configuration in &[
+                    Configuration {
+                        optimize: true,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        arithmetic: ArithmeticPolicy::Trap,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        extensions: true,
+                        ..Configuration::default()
+                    },
+                ] {
+                    let mut interpreter = Interpreter::with_configuration(configuration.clone());
+                    match interpreter.load_bytecode(&bytecode, b"+-") {
+                        Err(BytecodeError::ConfigurationMismatch) => {},
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            #[test]
+            fn malformed() {
+                let (mut interpreter, bytecode) = save(Configuration::default(), b"[]");
+                let header = &bytecode[..HEADER_SIZE];
+                let payloads: &[&[u8]] = &[
+                    &[],
+                    &[2, 6, 1, 1, 1, 1, 1],
+                    &[2, 6, 1, 1, 1, 1, 1, 7, 0, 1, 2, 1, 2, 5],
+                    &[1, 17, 1, 1, 1, 1],
+                    &[2, 6, 1, 1, 1, 1, 1, 7, 1, 1, 2, 1, 2],
+                    &[1, 6, 0, 1, 1, 1, 1],
+                    &[
+                        1, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 1,
+                        1, 1, 1,
+                    ],
+                ];
+                for payload in payloads {
+                    let mut bytecode = header.to_vec();
+                    bytecode[16..24].copy_from_slice(&hash(payload).to_le_bytes());
+                    bytecode.extend_from_slice(payload);
+                    match interpreter.load_bytecode(&bytecode, b"[]") {
+                        Err(BytecodeError::Malformed) => {},
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            #[test]
+            fn unproducible_instructions() {
+                let optimized = Configuration {
+                    optimize: true,
+                    ..Configuration::default()
+                };
+                let trapping = Configuration {
+                    arithmetic: ArithmeticPolicy::Trap,
+                    ..optimized.clone()
+                };
+                let cases = &[
+                    (Configuration::default(), Instruction::Increment(0)),
+                    (Configuration::default(), Instruction::SetZero),
+                    (Configuration::default(), Instruction::IncrementAt(1, 1)),
+                    (Configuration::default(), Instruction::Dump),
+                    (optimized.clone(), Instruction::ScanLeft(0)),
+                    (optimized.clone(), Instruction::WriteAt(0)),
+                    (optimized.clone(), Instruction::MultiplyAdd(vec![])),
+                    (optimized, Instruction::MultiplyAdd(vec![(0, 1)])),
+                    (trapping, Instruction::MultiplyAdd(vec![(1, 1)])),
+                ];
+                for (configuration, instruction) in cases {
+                    let (mut interpreter, bytecode) = save(configuration.clone(), b"+");
+                    let mut payload = Vec::new();
+                    write_unsigned(&mut payload, 1);
+                    encode(&mut payload, instruction);
+                    payload.extend_from_slice(&[1, 1, 1, 1]);
+
+                    let mut bytecode = bytecode[..HEADER_SIZE].to_vec();
+                    bytecode[16..24].copy_from_slice(&hash(&payload).to_le_bytes());
+                    bytecode.extend_from_slice(&payload);
+                    match interpreter.load_bytecode(&bytecode, b"+") {
+                        Err(BytecodeError::Malformed) => {},
+                        _ => unreachable!(),
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+mod c;
+mod rust;
+
+use crate::interpreter::Interpreter;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Target {
+    C,
+    Rust,
+}
+
+impl Interpreter {
+    pub fn compile(&self, target: Target) -> String {
+        match target {
+            Target::C => c::generate(self.program(), self.embedded_input(), self.configuration()),
+            Target::Rust => {
+                rust::generate(self.program(), self.embedded_input(), self.configuration())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod compile {
+            use crate::{
+                codegen::Target,
+                configuration::{
+                    ArithmeticPolicy,
+                    CellWidth,
+                    Configuration,
+                    TapeGrowth,
+                },
+                interpreter::Interpreter,
+            };
+            use std::{
+                env,
+                fs,
+                io::Write,
+                path::Path,
+                process::{
+                    self,
+                    Command,
+                    Stdio,
+                },
+            };
+
+            fn configurations() -> Vec<Configuration> {
+                vec![
+                    Configuration::default(),
+                    Configuration {
+                        cell_width: CellWidth::U16,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        arithmetic: ArithmeticPolicy::Saturate,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        arithmetic: ArithmeticPolicy::Trap,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        cell_width: CellWidth::U16,
+                        arithmetic: ArithmeticPolicy::Trap,
+                        optimize: true,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        tape_size: 1,
+                        tape_growth: TapeGrowth::Both,
+                        optimize: true,
+                        ..Configuration::default()
+                    },
+                ]
+            }
+
+            fn scripts(configuration: &Configuration) -> Vec<(Vec<u8>, &'static [u8])> {
+                let mut scripts = vec![
+                    (
+                        include_bytes!("../assets/99-bottles-of-beer.bf").to_vec(),
+                        &b""[..],
+                    ),
+                    (
+                        include_bytes!("../assets/cat.bf").to_vec(),
+                        b"Hello World!\n",
+                    ),
+                    (include_bytes!("../assets/hello-world.bf").to_vec(), b""),
+                    (
+                        include_bytes!("../assets/rot13.bf").to_vec(),
+                        b"Hello World!\n",
+                    ),
+                    (b"+[-<]".to_vec(), b""),
+                    (format!("+{}.", ">".repeat(30_000)).into_bytes(), b""),
+                    (b"+++[>++++++++++<-]>[>+<-]>-".to_vec(), b""),
+                ];
+                // The triangle relies on wrapping cells, so it never ends with
+                // saturating ones.
+                if configuration.arithmetic != ArithmeticPolicy::Saturate {
+                    scripts.push((
+                        include_bytes!("../assets/sierpinski-triangle.bf").to_vec(),
+                        b"",
+                    ));
+                }
+                scripts
+            }
+
+            // Runs the executable and returns its output, along with the error it
+            // reports, if any.
+            fn run(executable: &Path, input: &[u8]) -> (Vec<u8>, Option<String>) {
+                let mut child = Command::new(executable)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .unwrap();
+                child.stdin.take().unwrap().write_all(input).unwrap();
+                let output = child.wait_with_output().unwrap();
+                let error = String::from_utf8(output.stderr).unwrap();
+                assert_eq!(output.status.success(), error.is_empty());
+                (
+                    output.stdout,
+                    if error.is_empty() { None } else { Some(error) },
+                )
+            }
+
+            // Compiles every script with every configuration using the given
+            // compiler, and checks that the executables behave the same as the
+            // interpreter. It's skipped if the compiler is not installed.
+            fn check(target: Target, compiler: &str, arguments: &[&str]) {
+                if Command::new(compiler).arg("--version").output().is_err() {
+                    return;
+                }
+
+                let directory = env::temp_dir().join(format!("bfc-{:?}-{}", target, process::id()));
+                fs::create_dir_all(&directory).unwrap();
+                let source = directory.join("program");
+                let executable = directory.join("executable");
+
+                for configuration in configurations() {
+                    for (script, input) in scripts(&configuration) {
+                        let mut interpreter =
+                            Interpreter::with_configuration(configuration.clone());
+                        interpreter.load(&script).unwrap();
+
+                        let mut output = Vec::new();
+                        let error =
+                            interpreter
+                                .execute_with(input, &mut output)
+                                .err()
+                                .map(|error| {
+                                    format!(
+                                        "runtime error: {}\n",
+                                        error.without_location().message()
+                                    )
+                                });
+
+                        fs::write(&source, interpreter.compile(target)).unwrap();
+                        let compiled = Command::new(compiler)
+                            .args(arguments)
+                            .arg("-o")
+                            .arg(&executable)
+                            .arg(&source)
+                            .status()
+                            .unwrap();
+                        assert!(compiled.success());
+
+                        assert_eq!(run(&executable, input), (output, error));
+                    }
+                }
+
+                fs::remove_dir_all(&directory).unwrap();
+            }
+
+            #[test]
+            fn c() {
+                check(Target::C, "cc", &["-x", "c", "-std=c99", "-O1"]);
+            }
+
+            #[test]
+            fn rust() {
+                check(
+                    Target::Rust,
+                    "rustc",
+                    &["--edition", "2018", "--crate-name", "program"],
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,580 @@
+use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        CellWidth,
+        Configuration,
+        EndOfInputPolicy,
+    },
+    instruction::Instruction,
+    tape::DUMP_RADIUS,
+};
+use std::fmt::{
+    self,
+    Write,
+};
+
+pub(crate) fn generate(
+    program: &[Instruction],
+    embedded_input: Option<&[u8]>,
+    configuration: &Configuration,
+) -> String {
+    let mut source = String::new();
+    prelude(&mut source, embedded_input, configuration).unwrap();
+    main(&mut source, program).unwrap();
+    source
+}
+
+fn prelude(
+    source: &mut String,
+    embedded_input: Option<&[u8]>,
+    configuration: &Configuration,
+) -> fmt::Result {
+    let (cell, cell_max) = match configuration.cell_width {
+        CellWidth::U8 => ("uint8_t", "UINT8_MAX"),
+        CellWidth::U16 => ("uint16_t", "UINT16_MAX"),
+        CellWidth::U32 => ("uint32_t", "UINT32_MAX"),
+    };
+
+    writeln!(source, "#include <stdint.h>")?;
+    writeln!(source, "#include <stdio.h>")?;
+    writeln!(source, "#include <stdlib.h>")?;
+    writeln!(source, "#include <string.h>")?;
+    writeln!(source)?;
+    writeln!(source, "typedef {} bf_cell;", cell)?;
+    writeln!(source, "#define BF_CELL_MAX {}", cell_max)?;
+    writeln!(source)?;
+    writeln!(source, "static bf_cell *bf_tape;")?;
+    writeln!(
+        source,
+        "static size_t bf_tape_size = {};",
+        configuration.tape_size.max(1)
+    )?;
+    writeln!(source, "static size_t bf_head = 0;")?;
+    writeln!(source)?;
+    if let Some(embedded_input) = embedded_input {
+        // Every byte is escaped, so the string literal is valid regardless of the
+        // input.
+        write!(source, "static const char bf_input[] = \"")?;
+        for byte in embedded_input {
+            write!(source, "\\{:03o}", byte)?;
+        }
+        writeln!(source, "\";")?;
+        writeln!(source, "static size_t bf_input_index = 0;")?;
+        writeln!(source)?;
+    }
+
+    writeln!(source, "static void bf_fail(const char *message) {{")?;
+    writeln!(source, "    fflush(stdout);")?;
+    writeln!(
+        source,
+        "    fprintf(stderr, \"runtime error: %s\\n\", message);"
+    )?;
+    writeln!(source, "    free(bf_tape);")?;
+    writeln!(source, "    exit(1);")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+    writeln!(source, "static inline size_t bf_left(size_t distance) {{")?;
+    writeln!(source, "    if (distance <= bf_head) {{")?;
+    writeln!(source, "        return bf_head - distance;")?;
+    writeln!(source, "    }}")?;
+    if configuration.tape_growth.grows_left() {
+        writeln!(source, "    size_t missing = distance - bf_head;")?;
+        writeln!(
+            source,
+            "    bf_cell *tape = realloc(bf_tape, (bf_tape_size + missing) * sizeof(bf_cell));",
+        )?;
+        writeln!(source, "    if (!tape) {{")?;
+        writeln!(source, "        bf_fail(\"unable to grow the tape\");")?;
+        writeln!(source, "    }}")?;
+        writeln!(
+            source,
+            "    memmove(tape + missing, tape, bf_tape_size * sizeof(bf_cell));"
+        )?;
+        writeln!(source, "    memset(tape, 0, missing * sizeof(bf_cell));")?;
+        writeln!(source, "    bf_tape = tape;")?;
+        writeln!(source, "    bf_tape_size += missing;")?;
+        writeln!(source, "    bf_head += missing;")?;
+        writeln!(source, "    return 0;")?;
+    } else {
+        writeln!(
+            source,
+            "    bf_fail(\"attempted to access a negative cell\");"
+        )?;
+        writeln!(source, "    return 0;")?;
+    }
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "static inline size_t bf_right(size_t distance) {{")?;
+    writeln!(source, "    if (distance < bf_tape_size - bf_head) {{")?;
+    writeln!(source, "        return bf_head + distance;")?;
+    writeln!(source, "    }}")?;
+    if configuration.tape_growth.grows_right() {
+        writeln!(source, "    size_t size = bf_head + distance + 1;")?;
+        writeln!(
+            source,
+            "    bf_cell *tape = realloc(bf_tape, size * sizeof(bf_cell));"
+        )?;
+        writeln!(source, "    if (!tape) {{")?;
+        writeln!(source, "        bf_fail(\"unable to grow the tape\");")?;
+        writeln!(source, "    }}")?;
+        writeln!(
+            source,
+            "    memset(tape + bf_tape_size, 0, (size - bf_tape_size) * sizeof(bf_cell));",
+        )?;
+        writeln!(source, "    bf_tape = tape;")?;
+        writeln!(source, "    bf_tape_size = size;")?;
+        writeln!(source, "    return bf_head + distance;")?;
+    } else {
+        writeln!(
+            source,
+            "    bf_fail(\"attempted to access a cell, which is above the cell limit\");",
+        )?;
+        writeln!(source, "    return 0;")?;
+    }
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "static inline void bf_increment(size_t index, uint64_t amount) {{"
+    )?;
+    match configuration.arithmetic {
+        ArithmeticPolicy::Wrap => {
+            writeln!(
+                source,
+                "    bf_tape[index] = (bf_cell) (bf_tape[index] + amount);"
+            )?;
+        },
+        ArithmeticPolicy::Saturate => {
+            writeln!(
+                source,
+                "    if (amount > (uint64_t) (BF_CELL_MAX - bf_tape[index])) {{"
+            )?;
+            writeln!(source, "        bf_tape[index] = BF_CELL_MAX;")?;
+            writeln!(source, "    }} else {{")?;
+            writeln!(
+                source,
+                "        bf_tape[index] = (bf_cell) (bf_tape[index] + amount);"
+            )?;
+            writeln!(source, "    }}")?;
+        },
+        ArithmeticPolicy::Trap => {
+            writeln!(
+                source,
+                "    if (amount > (uint64_t) (BF_CELL_MAX - bf_tape[index])) {{"
+            )?;
+            writeln!(
+                source,
+                "        bf_fail(\"attempted to increment a cell above its maximum value\");",
+            )?;
+            writeln!(source, "    }}")?;
+            writeln!(
+                source,
+                "    bf_tape[index] = (bf_cell) (bf_tape[index] + amount);"
+            )?;
+        },
+    }
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "static inline void bf_decrement(size_t index, uint64_t amount) {{"
+    )?;
+    match configuration.arithmetic {
+        ArithmeticPolicy::Wrap => {
+            writeln!(
+                source,
+                "    bf_tape[index] = (bf_cell) (bf_tape[index] - amount);"
+            )?;
+        },
+        ArithmeticPolicy::Saturate => {
+            writeln!(source, "    if (amount > bf_tape[index]) {{")?;
+            writeln!(source, "        bf_tape[index] = 0;")?;
+            writeln!(source, "    }} else {{")?;
+            writeln!(
+                source,
+                "        bf_tape[index] = (bf_cell) (bf_tape[index] - amount);"
+            )?;
+            writeln!(source, "    }}")?;
+        },
+        ArithmeticPolicy::Trap => {
+            writeln!(source, "    if (amount > bf_tape[index]) {{")?;
+            writeln!(
+                source,
+                "        bf_fail(\"attempted to decrement a cell below zero\");"
+            )?;
+            writeln!(source, "    }}")?;
+            writeln!(
+                source,
+                "    bf_tape[index] = (bf_cell) (bf_tape[index] - amount);"
+            )?;
+        },
+    }
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "static inline void bf_multiply_add(size_t index, int64_t factor, bf_cell value) {{",
+    )?;
+    writeln!(
+        source,
+        "    bf_tape[index] = (bf_cell) (bf_tape[index] + (uint64_t) factor * value);",
+    )?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "static inline void bf_read(size_t index) {{")?;
+    if embedded_input.is_some() {
+        writeln!(source, "    int character = EOF;")?;
+        writeln!(source, "    if (bf_input_index < sizeof(bf_input) - 1) {{")?;
+        writeln!(
+            source,
+            "        character = (unsigned char) bf_input[bf_input_index++];"
+        )?;
+        writeln!(source, "    }}")?;
+    } else {
+        writeln!(source, "    int character = getchar();")?;
+    }
+    writeln!(source, "    if (character != EOF) {{")?;
+    writeln!(source, "        bf_tape[index] = (bf_cell) character;")?;
+    match configuration.end_of_input {
+        EndOfInputPolicy::Zero => {
+            writeln!(source, "    }} else {{")?;
+            writeln!(source, "        bf_tape[index] = 0;")?;
+        },
+        EndOfInputPolicy::Max => {
+            writeln!(source, "    }} else {{")?;
+            writeln!(source, "        bf_tape[index] = BF_CELL_MAX;")?;
+        },
+        EndOfInputPolicy::Unchanged => {},
+    }
+    writeln!(source, "    }}")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "static inline void bf_write(size_t index) {{")?;
+    writeln!(source, "    putchar((unsigned char) bf_tape[index]);")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    if configuration.extensions {
+        writeln!(source, "static void bf_dump(void) {{")?;
+        writeln!(
+            source,
+            "    size_t start = bf_head > {0} ? bf_head - {0} : 0;",
+            DUMP_RADIUS
+        )?;
+        writeln!(
+            source,
+            "    size_t end = bf_tape_size - 1 - bf_head > {0} ? bf_head + {0} : bf_tape_size - 1;",
+            DUMP_RADIUS,
+        )?;
+        writeln!(source, "    fflush(stdout);")?;
+        writeln!(
+            source,
+            "    fprintf(stderr, \"head = %zu, cells %zu..=%zu =\", bf_head, start, end);",
+        )?;
+        writeln!(
+            source,
+            "    for (size_t cell = start; cell <= end; cell++) {{"
+        )?;
+        writeln!(
+            source,
+            "        unsigned long value = (unsigned long) bf_tape[cell];"
+        )?;
+        writeln!(
+            source,
+            "        fprintf(stderr, cell == bf_head ? \" [%lu]\" : \" %lu\", value);",
+        )?;
+        writeln!(source, "    }}")?;
+        writeln!(source, "    fprintf(stderr, \"\\n\");")?;
+        writeln!(source, "}}")?;
+        writeln!(source)?;
+    }
+
+    Ok(())
+}
+
+fn main(source: &mut String, program: &[Instruction]) -> fmt::Result {
+    writeln!(source, "int main(void) {{")?;
+    writeln!(
+        source,
+        "    bf_tape = calloc(bf_tape_size, sizeof(bf_cell));"
+    )?;
+    writeln!(source, "    if (!bf_tape) {{")?;
+    writeln!(source, "        bf_fail(\"unable to allocate the tape\");")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    let mut depth = 1;
+    for instruction in program {
+        if let Instruction::EndLoop(_) = instruction {
+            depth -= 1;
+        }
+        let indentation = "    ".repeat(depth);
+        match *instruction {
+            Instruction::MoveLeft(amount) => {
+                writeln!(source, "{}bf_head = bf_left({});", indentation, amount)?;
+            },
+            Instruction::MoveRight(amount) => {
+                writeln!(source, "{}bf_head = bf_right({});", indentation, amount)?;
+            },
+            Instruction::Increment(amount) => {
+                writeln!(
+                    source,
+                    "{}bf_increment(bf_head, UINT64_C({}));",
+                    indentation, amount,
+                )?;
+            },
+            Instruction::Decrement(amount) => {
+                writeln!(
+                    source,
+                    "{}bf_decrement(bf_head, UINT64_C({}));",
+                    indentation, amount,
+                )?;
+            },
+            Instruction::Read => {
+                writeln!(source, "{}bf_read(bf_head);", indentation)?;
+            },
+            Instruction::Write => {
+                writeln!(source, "{}bf_write(bf_head);", indentation)?;
+            },
+            Instruction::StartLoop(_) => {
+                writeln!(source, "{}while (bf_tape[bf_head]) {{", indentation)?;
+                depth += 1;
+            },
+            Instruction::EndLoop(_) => {
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::SetZero => {
+                writeln!(source, "{}bf_tape[bf_head] = 0;", indentation)?;
+            },
+            Instruction::ScanLeft(amount) => {
+                writeln!(source, "{}while (bf_tape[bf_head]) {{", indentation)?;
+                writeln!(source, "{}    bf_head = bf_left({});", indentation, amount)?;
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::ScanRight(amount) => {
+                writeln!(source, "{}while (bf_tape[bf_head]) {{", indentation)?;
+                writeln!(source, "{}    bf_head = bf_right({});", indentation, amount)?;
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::MultiplyAdd(ref targets) => {
+                writeln!(source, "{}if (bf_tape[bf_head]) {{", indentation)?;
+                writeln!(
+                    source,
+                    "{}    bf_cell value = bf_tape[bf_head];",
+                    indentation
+                )?;
+                for &(offset, factor) in targets {
+                    writeln!(
+                        source,
+                        "{}    bf_multiply_add({}, INT64_C({}), value);",
+                        indentation,
+                        location(offset),
+                        factor,
+                    )?;
+                }
+                writeln!(source, "{}    bf_tape[bf_head] = 0;", indentation)?;
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::IncrementAt(offset, amount) => {
+                writeln!(
+                    source,
+                    "{}bf_increment({}, UINT64_C({}));",
+                    indentation,
+                    location(offset),
+                    amount,
+                )?;
+            },
+            Instruction::DecrementAt(offset, amount) => {
+                writeln!(
+                    source,
+                    "{}bf_decrement({}, UINT64_C({}));",
+                    indentation,
+                    location(offset),
+                    amount,
+                )?;
+            },
+            Instruction::ReadAt(offset) => {
+                writeln!(source, "{}bf_read({});", indentation, location(offset))?;
+            },
+            Instruction::WriteAt(offset) => {
+                writeln!(source, "{}bf_write({});", indentation, location(offset))?;
+            },
+            Instruction::Dump => {
+                writeln!(source, "{}bf_dump();", indentation)?;
+            },
+        }
+    }
+
+    writeln!(source)?;
+    writeln!(source, "    fflush(stdout);")?;
+    writeln!(source, "    free(bf_tape);")?;
+    writeln!(source, "    return 0;")?;
+    writeln!(source, "}}")?;
+
+    Ok(())
+}
+
+fn location(offset: isize) -> String {
+    if offset < 0 {
+        format!("bf_left({})", offset.unsigned_abs())
+    } else {
+        format!("bf_right({})", offset)
+    }
+}
+
+#[cfg(test)]
+mod functions {
+    mod generate {
+        use crate::{
+            codegen::Target,
+            configuration::{
+                ArithmeticPolicy,
+                CellWidth,
+                Configuration,
+                EndOfInputPolicy,
+                TapeGrowth,
+            },
+            interpreter::Interpreter,
+        };
+
+        fn generate(configuration: Configuration, script: &[u8]) -> String {
+            let mut interpreter = Interpreter::with_configuration(configuration);
+            interpreter.load(script).unwrap();
+            interpreter.compile(Target::C)
+        }
+
+        #[test]
+        fn program() {
+            let source = generate(Configuration::default(), b"+[->+<]>.<,");
+            let expected = "int main(void) {
+    bf_tape = calloc(bf_tape_size, sizeof(bf_cell));
+    if (!bf_tape) {
+        bf_fail(\"unable to allocate the tape\");
+    }
+
+    bf_increment(bf_head, UINT64_C(1));
+    while (bf_tape[bf_head]) {
+        bf_decrement(bf_head, UINT64_C(1));
+        bf_head = bf_right(1);
+        bf_increment(bf_head, UINT64_C(1));
+        bf_head = bf_left(1);
+    }
+    bf_head = bf_right(1);
+    bf_write(bf_head);
+    bf_head = bf_left(1);
+    bf_read(bf_head);
+
+    fflush(stdout);
+    free(bf_tape);
+    return 0;
+}
+";
+            assert!(source.ends_with(expected));
+        }
+
+        #[test]
+        fn optimized_program() {
+            let configuration = Configuration {
+                optimize: true,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"+[->+<]>.<<,[<]");
+            assert!(source.contains(
+                "    if (bf_tape[bf_head]) {
+        bf_cell value = bf_tape[bf_head];
+        bf_multiply_add(bf_right(1), INT64_C(1), value);
+        bf_tape[bf_head] = 0;
+    }
+    bf_write(bf_right(1));
+    bf_read(bf_left(1));
+    bf_head = bf_left(1);
+    while (bf_tape[bf_head]) {
+        bf_head = bf_left(1);
+    }
+"
+            ));
+        }
+
+        #[test]
+        fn tape() {
+            let source = generate(Configuration::default(), b"");
+            assert!(source.contains("typedef uint8_t bf_cell;"));
+            assert!(source.contains("static size_t bf_tape_size = 30000;"));
+            assert!(source.contains("bf_fail(\"attempted to access a negative cell\");"));
+
+            let configuration = Configuration {
+                tape_size: 42,
+                cell_width: CellWidth::U32,
+                tape_growth: TapeGrowth::Both,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains("typedef uint32_t bf_cell;"));
+            assert!(source.contains("#define BF_CELL_MAX UINT32_MAX"));
+            assert!(source.contains("static size_t bf_tape_size = 42;"));
+            assert!(!source.contains("bf_fail(\"attempted to access a negative cell\");"));
+        }
+
+        #[test]
+        fn arithmetic() {
+            let configuration = Configuration {
+                arithmetic: ArithmeticPolicy::Trap,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains("bf_fail(\"attempted to decrement a cell below zero\");"));
+        }
+
+        #[test]
+        fn end_of_input() {
+            let configuration = Configuration {
+                end_of_input: EndOfInputPolicy::Max,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains(
+                "    } else {
+        bf_tape[index] = BF_CELL_MAX;
+    }"
+            ));
+
+            let configuration = Configuration {
+                end_of_input: EndOfInputPolicy::Unchanged,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains(
+                "    if (character != EOF) {
+        bf_tape[index] = (bf_cell) character;
+    }
+}"
+            ));
+        }
+
+        #[test]
+        fn extensions() {
+            let source = generate(Configuration::default(), b"#!a");
+            assert!(!source.contains("bf_dump"));
+            assert!(!source.contains("bf_input"));
+
+            let configuration = Configuration {
+                extensions: true,
+                ..Configuration::default()
+            };
+            let source = generate(configuration.clone(), b"+#");
+            assert!(source.contains("static void bf_dump(void) {"));
+            assert!(source.contains("    bf_dump();\n"));
+            assert!(source.contains("int character = getchar();"));
+
+            let source = generate(configuration, b",!a\"\0");
+            assert!(source.contains("static const char bf_input[] = \"\\141\\042\\000\";"));
+            assert!(!source.contains("getchar()"));
+        }
+    }
+}
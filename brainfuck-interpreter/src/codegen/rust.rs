@@ -0,0 +1,663 @@
+use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        CellWidth,
+        Configuration,
+        EndOfInputPolicy,
+    },
+    instruction::Instruction,
+    tape::DUMP_RADIUS,
+};
+use std::{
+    ascii,
+    fmt::{
+        self,
+        Write,
+    },
+};
+
+pub(crate) fn generate(
+    program: &[Instruction],
+    embedded_input: Option<&[u8]>,
+    configuration: &Configuration,
+) -> String {
+    let mut source = String::new();
+    prelude(&mut source, embedded_input, configuration).unwrap();
+    run(&mut source, program).unwrap();
+    main(&mut source, embedded_input).unwrap();
+    source
+}
+
+fn prelude(
+    source: &mut String,
+    embedded_input: Option<&[u8]>,
+    configuration: &Configuration,
+) -> fmt::Result {
+    let cell = match configuration.cell_width {
+        CellWidth::U8 => "u8",
+        CellWidth::U16 => "u16",
+        CellWidth::U32 => "u32",
+    };
+
+    writeln!(source, "#![allow(dead_code)]")?;
+    writeln!(source)?;
+    writeln!(source, "use std::{{")?;
+    writeln!(source, "    fmt::{{")?;
+    writeln!(source, "        self,")?;
+    writeln!(source, "        Display,")?;
+    writeln!(source, "    }},")?;
+    writeln!(source, "    io::{{")?;
+    writeln!(source, "        self,")?;
+    writeln!(source, "        BufReader,")?;
+    writeln!(source, "        Bytes,")?;
+    writeln!(source, "        Read,")?;
+    writeln!(source, "        Write,")?;
+    writeln!(source, "    }},")?;
+    writeln!(source, "    process,")?;
+    writeln!(source, "}};")?;
+    writeln!(source)?;
+    writeln!(source, "type Cell = {};", cell)?;
+    writeln!(source)?;
+    writeln!(
+        source,
+        "const TAPE_SIZE: usize = {};",
+        configuration.tape_size.max(1)
+    )?;
+    writeln!(source)?;
+    if configuration.extensions {
+        writeln!(source, "const DUMP_RADIUS: usize = {};", DUMP_RADIUS)?;
+        writeln!(source)?;
+    }
+    if let Some(embedded_input) = embedded_input {
+        write!(source, "const INPUT: &[u8] = b\"")?;
+        for &byte in embedded_input {
+            write!(source, "{}", ascii::escape_default(byte))?;
+        }
+        writeln!(source, "\";")?;
+        writeln!(source)?;
+    }
+
+    writeln!(source, "#[derive(Debug)]")?;
+    writeln!(source, "enum RuntimeError {{")?;
+    writeln!(source, "    Io(io::Error),")?;
+    writeln!(source, "    CellUnderflow,")?;
+    writeln!(source, "    CellOverflow,")?;
+    writeln!(source, "    ValueUnderflow,")?;
+    writeln!(source, "    ValueOverflow,")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "impl Display for RuntimeError {{")?;
+    writeln!(
+        source,
+        "    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {{"
+    )?;
+    writeln!(source, "        match *self {{")?;
+    writeln!(source, "            RuntimeError::Io(ref error) => {{")?;
+    writeln!(
+        source,
+        "                write!(f, \"runtime error: unexpected io error occurred ({{}})\", error)",
+    )?;
+    writeln!(source, "            }},")?;
+    writeln!(source, "            RuntimeError::CellUnderflow => {{")?;
+    writeln!(
+        source,
+        "                write!(f, \"runtime error: attempted to access a negative cell\")",
+    )?;
+    writeln!(source, "            }},")?;
+    writeln!(source, "            RuntimeError::CellOverflow => {{")?;
+    writeln!(source, "                write!(")?;
+    writeln!(source, "                    f,")?;
+    writeln!(
+        source,
+        "                    \"runtime error: attempted to access a cell, which is above the cell \
+         limit\",",
+    )?;
+    writeln!(source, "                )")?;
+    writeln!(source, "            }},")?;
+    writeln!(source, "            RuntimeError::ValueUnderflow => {{")?;
+    writeln!(
+        source,
+        "                write!(f, \"runtime error: attempted to decrement a cell below zero\")",
+    )?;
+    writeln!(source, "            }},")?;
+    writeln!(source, "            RuntimeError::ValueOverflow => {{")?;
+    writeln!(source, "                write!(")?;
+    writeln!(source, "                    f,")?;
+    writeln!(
+        source,
+        "                    \"runtime error: attempted to increment a cell above its maximum \
+         value\",",
+    )?;
+    writeln!(source, "                )")?;
+    writeln!(source, "            }},")?;
+    writeln!(source, "        }}")?;
+    writeln!(source, "    }}")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "impl From<io::Error> for RuntimeError {{")?;
+    writeln!(source, "    fn from(error: io::Error) -> RuntimeError {{")?;
+    writeln!(source, "        RuntimeError::Io(error)")?;
+    writeln!(source, "    }}")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "struct Machine<R: Read, W: Write> {{")?;
+    writeln!(source, "    tape: Vec<Cell>,")?;
+    writeln!(source, "    head: usize,")?;
+    writeln!(source, "    input: Bytes<BufReader<R>>,")?;
+    writeln!(source, "    output: W,")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    writeln!(source, "impl<R: Read, W: Write> Machine<R, W> {{")?;
+
+    writeln!(
+        source,
+        "    fn locate(&mut self, offset: isize) -> Result<usize, RuntimeError> {{"
+    )?;
+    writeln!(source, "        if offset < 0 {{")?;
+    writeln!(source, "            let distance = offset.unsigned_abs();")?;
+    writeln!(source, "            if distance <= self.head {{")?;
+    writeln!(source, "                return Ok(self.head - distance);")?;
+    writeln!(source, "            }}")?;
+    if configuration.tape_growth.grows_left() {
+        writeln!(source, "            let missing = distance - self.head;")?;
+        writeln!(
+            source,
+            "            self.tape.splice(0..0, std::iter::repeat(0).take(missing));",
+        )?;
+        writeln!(source, "            self.head += missing;")?;
+        writeln!(source, "            Ok(0)")?;
+    } else {
+        writeln!(source, "            Err(RuntimeError::CellUnderflow)")?;
+    }
+    writeln!(source, "        }} else {{")?;
+    writeln!(
+        source,
+        "            let index = self.head + offset as usize;"
+    )?;
+    writeln!(source, "            if index < self.tape.len() {{")?;
+    writeln!(source, "                return Ok(index);")?;
+    writeln!(source, "            }}")?;
+    if configuration.tape_growth.grows_right() {
+        writeln!(source, "            self.tape.resize(index + 1, 0);")?;
+        writeln!(source, "            Ok(index)")?;
+    } else {
+        writeln!(source, "            Err(RuntimeError::CellOverflow)")?;
+    }
+    writeln!(source, "        }}")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "    fn seek(&mut self, offset: isize) -> Result<(), RuntimeError> {{"
+    )?;
+    writeln!(source, "        self.head = self.locate(offset)?;")?;
+    writeln!(source, "        Ok(())")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(source, "    fn current(&self) -> Cell {{")?;
+    writeln!(source, "        self.tape[self.head]")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(source, "    fn clear(&mut self) {{")?;
+    writeln!(source, "        self.tape[self.head] = 0;")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "    fn increment(&mut self, offset: isize, amount: u64) -> Result<(), RuntimeError> {{",
+    )?;
+    writeln!(source, "        let index = self.locate(offset)?;")?;
+    writeln!(source, "        let cell = self.tape[index];")?;
+    match configuration.arithmetic {
+        ArithmeticPolicy::Wrap => {
+            writeln!(
+                source,
+                "        self.tape[index] = (cell as u64).wrapping_add(amount) as Cell;",
+            )?;
+        },
+        ArithmeticPolicy::Saturate => {
+            writeln!(
+                source,
+                "        self.tape[index] = if amount > (Cell::MAX - cell) as u64 {{",
+            )?;
+            writeln!(source, "            Cell::MAX")?;
+            writeln!(source, "        }} else {{")?;
+            writeln!(source, "            cell + amount as Cell")?;
+            writeln!(source, "        }};")?;
+        },
+        ArithmeticPolicy::Trap => {
+            writeln!(source, "        if amount > (Cell::MAX - cell) as u64 {{")?;
+            writeln!(
+                source,
+                "            return Err(RuntimeError::ValueOverflow);"
+            )?;
+            writeln!(source, "        }}")?;
+            writeln!(source, "        self.tape[index] = cell + amount as Cell;")?;
+        },
+    }
+    writeln!(source, "        Ok(())")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "    fn decrement(&mut self, offset: isize, amount: u64) -> Result<(), RuntimeError> {{",
+    )?;
+    writeln!(source, "        let index = self.locate(offset)?;")?;
+    writeln!(source, "        let cell = self.tape[index];")?;
+    match configuration.arithmetic {
+        ArithmeticPolicy::Wrap => {
+            writeln!(
+                source,
+                "        self.tape[index] = (cell as u64).wrapping_sub(amount) as Cell;",
+            )?;
+        },
+        ArithmeticPolicy::Saturate => {
+            writeln!(
+                source,
+                "        self.tape[index] = if amount > cell as u64 {{"
+            )?;
+            writeln!(source, "            0")?;
+            writeln!(source, "        }} else {{")?;
+            writeln!(source, "            cell - amount as Cell")?;
+            writeln!(source, "        }};")?;
+        },
+        ArithmeticPolicy::Trap => {
+            writeln!(source, "        if amount > cell as u64 {{")?;
+            writeln!(
+                source,
+                "            return Err(RuntimeError::ValueUnderflow);"
+            )?;
+            writeln!(source, "        }}")?;
+            writeln!(source, "        self.tape[index] = cell - amount as Cell;")?;
+        },
+    }
+    writeln!(source, "        Ok(())")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "    fn multiply_add(&mut self, offset: isize, factor: i64, value: Cell) -> Result<(), \
+         RuntimeError> {{",
+    )?;
+    writeln!(source, "        let index = self.locate(offset)?;")?;
+    writeln!(
+        source,
+        "        let product = (factor as u64).wrapping_mul(value as u64);"
+    )?;
+    writeln!(
+        source,
+        "        self.tape[index] = (self.tape[index] as u64).wrapping_add(product) as Cell;",
+    )?;
+    writeln!(source, "        Ok(())")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "    fn read(&mut self, offset: isize) -> Result<(), RuntimeError> {{"
+    )?;
+    writeln!(source, "        let index = self.locate(offset)?;")?;
+    writeln!(source, "        match self.input.next() {{")?;
+    writeln!(
+        source,
+        "            Some(byte) => self.tape[index] = byte? as Cell,"
+    )?;
+    match configuration.end_of_input {
+        EndOfInputPolicy::Zero => {
+            writeln!(source, "            None => self.tape[index] = 0,")?;
+        },
+        EndOfInputPolicy::Max => {
+            writeln!(source, "            None => self.tape[index] = Cell::MAX,")?;
+        },
+        EndOfInputPolicy::Unchanged => {
+            writeln!(source, "            None => {{}},")?;
+        },
+    }
+    writeln!(source, "        }}")?;
+    writeln!(source, "        Ok(())")?;
+    writeln!(source, "    }}")?;
+    writeln!(source)?;
+
+    writeln!(
+        source,
+        "    fn write(&mut self, offset: isize) -> Result<(), RuntimeError> {{"
+    )?;
+    writeln!(source, "        let index = self.locate(offset)?;")?;
+    writeln!(
+        source,
+        "        self.output.write_all(&[self.tape[index] as u8])?;"
+    )?;
+    writeln!(source, "        Ok(())")?;
+    writeln!(source, "    }}")?;
+
+    if configuration.extensions {
+        writeln!(source)?;
+        writeln!(
+            source,
+            "    fn dump(&mut self) -> Result<(), RuntimeError> {{"
+        )?;
+        writeln!(source, "        self.output.flush()?;")?;
+        writeln!(
+            source,
+            "        let start = self.head.saturating_sub(DUMP_RADIUS);"
+        )?;
+        writeln!(
+            source,
+            "        let end = self.head.saturating_add(DUMP_RADIUS).min(self.tape.len() - 1);",
+        )?;
+        writeln!(source, "        let stderr = io::stderr();")?;
+        writeln!(source, "        let mut stderr = stderr.lock();")?;
+        writeln!(
+            source,
+            "        write!(stderr, \"head = {{}}, cells {{}}..={{}} =\", self.head, start, end)?;",
+        )?;
+        writeln!(source, "        for cell in start..=end {{")?;
+        writeln!(source, "            if cell == self.head {{")?;
+        writeln!(
+            source,
+            "                write!(stderr, \" [{{}}]\", self.tape[cell])?;"
+        )?;
+        writeln!(source, "            }} else {{")?;
+        writeln!(
+            source,
+            "                write!(stderr, \" {{}}\", self.tape[cell])?;"
+        )?;
+        writeln!(source, "            }}")?;
+        writeln!(source, "        }}")?;
+        writeln!(source, "        writeln!(stderr)?;")?;
+        writeln!(source, "        Ok(())")?;
+        writeln!(source, "    }}")?;
+    }
+
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    Ok(())
+}
+
+fn run(source: &mut String, program: &[Instruction]) -> fmt::Result {
+    writeln!(
+        source,
+        "fn run<R: Read, W: Write>(machine: &mut Machine<R, W>) -> Result<(), RuntimeError> {{",
+    )?;
+
+    let mut depth = 1;
+    for instruction in program {
+        if let Instruction::EndLoop(_) = instruction {
+            depth -= 1;
+        }
+        let indentation = "    ".repeat(depth);
+        match *instruction {
+            Instruction::MoveLeft(amount) => {
+                writeln!(source, "{}machine.seek(-{})?;", indentation, amount)?;
+            },
+            Instruction::MoveRight(amount) => {
+                writeln!(source, "{}machine.seek({})?;", indentation, amount)?;
+            },
+            Instruction::Increment(amount) => {
+                writeln!(source, "{}machine.increment(0, {})?;", indentation, amount)?;
+            },
+            Instruction::Decrement(amount) => {
+                writeln!(source, "{}machine.decrement(0, {})?;", indentation, amount)?;
+            },
+            Instruction::Read => {
+                writeln!(source, "{}machine.read(0)?;", indentation)?;
+            },
+            Instruction::Write => {
+                writeln!(source, "{}machine.write(0)?;", indentation)?;
+            },
+            Instruction::StartLoop(_) => {
+                writeln!(source, "{}while machine.current() != 0 {{", indentation)?;
+                depth += 1;
+            },
+            Instruction::EndLoop(_) => {
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::SetZero => {
+                writeln!(source, "{}machine.clear();", indentation)?;
+            },
+            Instruction::ScanLeft(amount) => {
+                writeln!(source, "{}while machine.current() != 0 {{", indentation)?;
+                writeln!(source, "{}    machine.seek(-{})?;", indentation, amount)?;
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::ScanRight(amount) => {
+                writeln!(source, "{}while machine.current() != 0 {{", indentation)?;
+                writeln!(source, "{}    machine.seek({})?;", indentation, amount)?;
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::MultiplyAdd(ref targets) => {
+                writeln!(source, "{}if machine.current() != 0 {{", indentation)?;
+                writeln!(source, "{}    let value = machine.current();", indentation)?;
+                for &(offset, factor) in targets {
+                    writeln!(
+                        source,
+                        "{}    machine.multiply_add({}, {}, value)?;",
+                        indentation, offset, factor,
+                    )?;
+                }
+                writeln!(source, "{}    machine.clear();", indentation)?;
+                writeln!(source, "{}}}", indentation)?;
+            },
+            Instruction::IncrementAt(offset, amount) => {
+                writeln!(
+                    source,
+                    "{}machine.increment({}, {})?;",
+                    indentation, offset, amount,
+                )?;
+            },
+            Instruction::DecrementAt(offset, amount) => {
+                writeln!(
+                    source,
+                    "{}machine.decrement({}, {})?;",
+                    indentation, offset, amount,
+                )?;
+            },
+            Instruction::ReadAt(offset) => {
+                writeln!(source, "{}machine.read({})?;", indentation, offset)?;
+            },
+            Instruction::WriteAt(offset) => {
+                writeln!(source, "{}machine.write({})?;", indentation, offset)?;
+            },
+            Instruction::Dump => {
+                writeln!(source, "{}machine.dump()?;", indentation)?;
+            },
+        }
+    }
+
+    writeln!(source, "    Ok(())")?;
+    writeln!(source, "}}")?;
+    writeln!(source)?;
+
+    Ok(())
+}
+
+fn main(source: &mut String, embedded_input: Option<&[u8]>) -> fmt::Result {
+    writeln!(source, "fn main() {{")?;
+    if embedded_input.is_none() {
+        writeln!(source, "    let stdin = io::stdin();")?;
+    }
+    writeln!(source, "    let stdout = io::stdout();")?;
+    writeln!(source, "    let mut machine = Machine {{")?;
+    writeln!(source, "        tape: vec![0; TAPE_SIZE],")?;
+    writeln!(source, "        head: 0,")?;
+    if embedded_input.is_some() {
+        writeln!(source, "        input: BufReader::new(INPUT).bytes(),")?;
+    } else {
+        writeln!(
+            source,
+            "        input: BufReader::new(stdin.lock()).bytes(),"
+        )?;
+    }
+    writeln!(source, "        output: stdout.lock(),")?;
+    writeln!(source, "    }};")?;
+    writeln!(source)?;
+    writeln!(source, "    let result = run(&mut machine);")?;
+    writeln!(source, "    let flushed = machine.output.flush();")?;
+    writeln!(
+        source,
+        "    if let Err(error) = result.and(flushed.map_err(RuntimeError::from)) {{"
+    )?;
+    writeln!(source, "        eprintln!(\"{{}}\", error);")?;
+    writeln!(source, "        process::exit(1);")?;
+    writeln!(source, "    }}")?;
+    writeln!(source, "}}")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod functions {
+    mod generate {
+        use crate::{
+            codegen::Target,
+            configuration::{
+                ArithmeticPolicy,
+                CellWidth,
+                Configuration,
+                EndOfInputPolicy,
+                TapeGrowth,
+            },
+            interpreter::Interpreter,
+        };
+
+        fn generate(configuration: Configuration, script: &[u8]) -> String {
+            let mut interpreter = Interpreter::with_configuration(configuration);
+            interpreter.load(script).unwrap();
+            interpreter.compile(Target::Rust)
+        }
+
+        #[test]
+        fn program() {
+            let source = generate(Configuration::default(), b"+[->+<]>.<,");
+            let expected = "fn run<R: Read, W: Write>(machine: &mut Machine<R, W>) -> Result<(), \
+                            RuntimeError> {
+    machine.increment(0, 1)?;
+    while machine.current() != 0 {
+        machine.decrement(0, 1)?;
+        machine.seek(1)?;
+        machine.increment(0, 1)?;
+        machine.seek(-1)?;
+    }
+    machine.seek(1)?;
+    machine.write(0)?;
+    machine.seek(-1)?;
+    machine.read(0)?;
+    Ok(())
+}
+";
+            assert!(source.contains(expected));
+            assert!(source.ends_with("        process::exit(1);\n    }\n}\n"));
+        }
+
+        #[test]
+        fn optimized_program() {
+            let configuration = Configuration {
+                optimize: true,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"+[->+<]>.<<,[<]");
+            assert!(source.contains(
+                "    if machine.current() != 0 {
+        let value = machine.current();
+        machine.multiply_add(1, 1, value)?;
+        machine.clear();
+    }
+    machine.write(1)?;
+    machine.read(-1)?;
+    machine.seek(-1)?;
+    while machine.current() != 0 {
+        machine.seek(-1)?;
+    }
+"
+            ));
+        }
+
+        #[test]
+        fn tape() {
+            let source = generate(Configuration::default(), b"");
+            assert!(source.contains("type Cell = u8;"));
+            assert!(source.contains("const TAPE_SIZE: usize = 30000;"));
+            assert!(source.contains("Err(RuntimeError::CellUnderflow)"));
+
+            let configuration = Configuration {
+                tape_size: 42,
+                cell_width: CellWidth::U32,
+                tape_growth: TapeGrowth::Both,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains("type Cell = u32;"));
+            assert!(source.contains("const TAPE_SIZE: usize = 42;"));
+            assert!(!source.contains("Err(RuntimeError::CellUnderflow)"));
+            assert!(!source.contains("Err(RuntimeError::CellOverflow)"));
+        }
+
+        #[test]
+        fn arithmetic() {
+            let configuration = Configuration {
+                arithmetic: ArithmeticPolicy::Trap,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains("return Err(RuntimeError::ValueUnderflow);"));
+            assert!(source.contains("return Err(RuntimeError::ValueOverflow);"));
+
+            let configuration = Configuration {
+                arithmetic: ArithmeticPolicy::Saturate,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(!source.contains("return Err(RuntimeError::ValueUnderflow);"));
+            assert!(source.contains("            Cell::MAX\n"));
+        }
+
+        #[test]
+        fn end_of_input() {
+            let configuration = Configuration {
+                end_of_input: EndOfInputPolicy::Max,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains("None => self.tape[index] = Cell::MAX,"));
+
+            let configuration = Configuration {
+                end_of_input: EndOfInputPolicy::Unchanged,
+                ..Configuration::default()
+            };
+            let source = generate(configuration, b"");
+            assert!(source.contains("None => {},"));
+        }
+
+        #[test]
+        fn extensions() {
+            let source = generate(Configuration::default(), b"#!a");
+            assert!(!source.contains("fn dump("));
+            assert!(!source.contains("INPUT"));
+
+            let configuration = Configuration {
+                extensions: true,
+                ..Configuration::default()
+            };
+            let source = generate(configuration.clone(), b"+#");
+            assert!(source.contains("const DUMP_RADIUS: usize = 8;"));
+            assert!(source.contains("    machine.dump()?;\n"));
+            assert!(source.contains("input: BufReader::new(stdin.lock()).bytes(),"));
+
+            let source = generate(configuration, b",!a\"\0");
+            assert!(source.contains("const INPUT: &[u8] = b\"a\\\"\\x00\";"));
+            assert!(source.contains("input: BufReader::new(INPUT).bytes(),"));
+            assert!(!source.contains("stdin"));
+        }
+    }
+}
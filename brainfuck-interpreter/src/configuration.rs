@@ -0,0 +1,182 @@
+use crate::interpreter::MEMORY_SIZE;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CellWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl CellWidth {
+    #[inline]
+    pub fn bits(self) -> u32 {
+        match self {
+            CellWidth::U8 => 8,
+            CellWidth::U16 => 16,
+            CellWidth::U32 => 32,
+        }
+    }
+
+    #[inline]
+    pub fn max(self) -> u32 {
+        match self {
+            CellWidth::U8 => u32::from(u8::MAX),
+            CellWidth::U16 => u32::from(u16::MAX),
+            CellWidth::U32 => u32::MAX,
+        }
+    }
+}
+
+impl Default for CellWidth {
+    #[inline]
+    fn default() -> CellWidth {
+        CellWidth::U8
+    }
+}
+
+#[cfg(test)]
+mod cell_width {
+    mod methods {
+        use crate::configuration::CellWidth;
+
+        #[test]
+        fn bits() {
+            assert_eq!(CellWidth::U8.bits(), 8);
+            assert_eq!(CellWidth::U16.bits(), 16);
+            assert_eq!(CellWidth::U32.bits(), 32);
+        }
+
+        #[test]
+        fn max() {
+            assert_eq!(CellWidth::U8.max(), 255);
+            assert_eq!(CellWidth::U16.max(), 65_535);
+            assert_eq!(CellWidth::U32.max(), 4_294_967_295);
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TapeGrowth {
+    Fixed,
+    Right,
+    Both,
+}
+
+impl TapeGrowth {
+    #[inline]
+    pub fn grows_left(self) -> bool {
+        self == TapeGrowth::Both
+    }
+
+    #[inline]
+    pub fn grows_right(self) -> bool {
+        self != TapeGrowth::Fixed
+    }
+}
+
+impl Default for TapeGrowth {
+    #[inline]
+    fn default() -> TapeGrowth {
+        TapeGrowth::Fixed
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ArithmeticPolicy {
+    Wrap,
+    Saturate,
+    Trap,
+}
+
+impl Default for ArithmeticPolicy {
+    #[inline]
+    fn default() -> ArithmeticPolicy {
+        ArithmeticPolicy::Wrap
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum EndOfInputPolicy {
+    Zero,
+    Max,
+    Unchanged,
+}
+
+impl Default for EndOfInputPolicy {
+    #[inline]
+    fn default() -> EndOfInputPolicy {
+        EndOfInputPolicy::Zero
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Configuration {
+    pub tape_size: usize,
+    pub cell_width: CellWidth,
+    pub tape_growth: TapeGrowth,
+    pub arithmetic: ArithmeticPolicy,
+    pub end_of_input: EndOfInputPolicy,
+    pub optimize: bool,
+    pub extensions: bool,
+    pub max_steps: Option<u64>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Configuration {
+    #[inline]
+    fn default() -> Configuration {
+        Configuration {
+            tape_size: MEMORY_SIZE,
+            cell_width: CellWidth::default(),
+            tape_growth: TapeGrowth::default(),
+            arithmetic: ArithmeticPolicy::default(),
+            end_of_input: EndOfInputPolicy::default(),
+            optimize: false,
+            extensions: false,
+            max_steps: None,
+            timeout: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod configuration {
+    mod traits {
+        mod default {
+            use crate::{
+                configuration::{
+                    ArithmeticPolicy,
+                    CellWidth,
+                    Configuration,
+                    EndOfInputPolicy,
+                    TapeGrowth,
+                },
+                interpreter::MEMORY_SIZE,
+            };
+
+            #[test]
+            fn default() {
+                let configuration = Configuration::default();
+                assert_eq!(
+                    configuration,
+                    Configuration {
+                        tape_size: MEMORY_SIZE,
+                        cell_width: CellWidth::U8,
+                        tape_growth: TapeGrowth::Fixed,
+                        arithmetic: ArithmeticPolicy::Wrap,
+                        end_of_input: EndOfInputPolicy::Zero,
+                        optimize: false,
+                        extensions: false,
+                        max_steps: None,
+                        timeout: None,
+                    },
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,540 @@
+use crate::{
+    error::RuntimeError,
+    interpreter::Interpreter,
+    machine::Machine,
+    span::Location,
+    tape::Tape,
+};
+use std::{
+    fs,
+    io::{
+        self,
+        BufRead,
+        Read,
+        Write,
+    },
+};
+
+const HELP: &str = "\
+commands:
+  break <line>:<column>     stops before the first instruction at or after the position
+  delete <line>:<column>    removes the breakpoint at or after the position
+  step [count]              executes the next instruction(s)
+  continue                  executes until a breakpoint, a watched cell changes or the end
+  watch <cell>              stops whenever the cell changes
+  unwatch <cell>            stops watching the cell
+  tape [radius]             prints the cells around the head
+  save <file>               writes a snapshot of the program to the file
+  restore <file>            rewinds the program to the snapshot in the file
+  help                      prints this message
+  quit                      stops debugging
+";
+
+impl Interpreter {
+    pub fn debug(&self) -> Result<Tape, RuntimeError> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        self.debug_with(stdin.lock(), stdout.lock())
+    }
+
+    pub fn debug_with<R: BufRead, W: Write>(
+        &self,
+        input: R,
+        output: W,
+    ) -> Result<Tape, RuntimeError> {
+        let mut debugger = Debugger {
+            interpreter: self,
+            machine: Machine::new(self),
+            input,
+            output,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+            halted: None,
+        };
+        debugger.run()?;
+        match debugger.halted {
+            Some(error) => Err(error),
+            None => Ok(debugger.machine.into_tape()),
+        }
+    }
+}
+
+struct Debugger<'a, R: BufRead, W: Write> {
+    interpreter: &'a Interpreter,
+    machine: Machine<'a>,
+    input: R,
+    output: W,
+    breakpoints: Vec<usize>,
+    watches: Vec<usize>,
+    // The runtime error, which halted the program, so the tape can still be inspected after it.
+    halted: Option<RuntimeError>,
+}
+
+impl<R: BufRead, W: Write> Debugger<'_, R, W> {
+    fn run(&mut self) -> Result<(), RuntimeError> {
+        self.report_location()?;
+        loop {
+            write!(self.output, "(bfdb) ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                writeln!(self.output)?;
+                return Ok(());
+            }
+
+            let mut words = line.split_whitespace();
+            let command = match words.next() {
+                Some(command) => command,
+                None => continue,
+            };
+            let argument = words.next();
+            match command {
+                "b" | "break" => {
+                    match argument.and_then(parse_location) {
+                        Some(location) => self.add_breakpoint(location)?,
+                        None => writeln!(self.output, "usage: break <line>:<column>")?,
+                    }
+                },
+                "d" | "delete" => {
+                    match argument.and_then(parse_location) {
+                        Some(location) => self.delete_breakpoint(location)?,
+                        None => writeln!(self.output, "usage: delete <line>:<column>")?,
+                    }
+                },
+                "s" | "step" => {
+                    match argument.map(str::parse).unwrap_or(Ok(1)) {
+                        Ok(count) => self.resume(Some(count))?,
+                        Err(_) => writeln!(self.output, "usage: step [count]")?,
+                    }
+                },
+                "c" | "continue" => {
+                    self.resume(None)?;
+                },
+                "w" | "watch" => {
+                    match argument.map(str::parse) {
+                        Some(Ok(cell)) => {
+                            if !self.watches.contains(&cell) {
+                                self.watches.push(cell);
+                            }
+                            writeln!(self.output, "watching cell {}", cell)?;
+                        },
+                        _ => writeln!(self.output, "usage: watch <cell>")?,
+                    }
+                },
+                "u" | "unwatch" => {
+                    match argument.map(str::parse) {
+                        Some(Ok(cell)) => {
+                            self.watches.retain(|&watch| watch != cell);
+                            writeln!(self.output, "stopped watching cell {}", cell)?;
+                        },
+                        _ => writeln!(self.output, "usage: unwatch <cell>")?,
+                    }
+                },
+                "t" | "tape" => {
+                    match argument.map(str::parse).unwrap_or(Ok(5)) {
+                        Ok(radius) => self.print_tape(radius)?,
+                        Err(_) => writeln!(self.output, "usage: tape [radius]")?,
+                    }
+                },
+                "save" => {
+                    match argument {
+                        Some(path) => self.save(path)?,
+                        None => writeln!(self.output, "usage: save <file>")?,
+                    }
+                },
+                "restore" => {
+                    match argument {
+                        Some(path) => self.restore(path)?,
+                        None => writeln!(self.output, "usage: restore <file>")?,
+                    }
+                },
+                "h" | "help" => {
+                    write!(self.output, "{}", HELP)?;
+                },
+                "q" | "quit" => {
+                    return Ok(());
+                },
+                _ => {
+                    writeln!(
+                        self.output,
+                        "unknown command '{}', type 'help' for the list of commands",
+                        command,
+                    )?;
+                },
+            }
+        }
+    }
+
+    fn resume(&mut self, mut remaining_steps: Option<usize>) -> Result<(), RuntimeError> {
+        if self.machine.is_finished() {
+            writeln!(self.output, "the program has finished")?;
+            return Ok(());
+        }
+        if self.halted.is_some() {
+            writeln!(self.output, "the program has halted")?;
+            return Ok(());
+        }
+
+        loop {
+            if remaining_steps == Some(0) {
+                break;
+            }
+
+            let watched_cells = self.watched_cells();
+            // The embedded input is queued in the machine, so it's part of the snapshots.
+            let result = if self.interpreter.embedded_input().is_some() {
+                self.machine.step_buffered(&mut self.output)
+            } else {
+                self.machine
+                    .step(&mut (&mut self.input).bytes(), &mut self.output)
+            };
+            match result {
+                Err(error @ RuntimeError::Io(_)) => return Err(error),
+                Err(error) => {
+                    writeln!(self.output, "{}", error)?;
+                    self.halted = Some(error);
+                    break;
+                },
+                Ok(()) => {},
+            }
+            remaining_steps = remaining_steps.map(|steps| steps - 1);
+
+            let mut watched_cell_changed = false;
+            for (&cell, old_value) in self.watches.iter().zip(watched_cells) {
+                let new_value = self.machine.tape().get(cell);
+                if new_value != old_value {
+                    writeln!(
+                        self.output,
+                        "cell {} changed from {} to {}",
+                        cell,
+                        old_value.unwrap_or(0),
+                        new_value.unwrap_or(0),
+                    )?;
+                    watched_cell_changed = true;
+                }
+            }
+
+            if self.machine.is_finished() || watched_cell_changed {
+                break;
+            }
+            if self.breakpoints.contains(&self.machine.instruction()) {
+                writeln!(self.output, "hit a breakpoint")?;
+                break;
+            }
+        }
+
+        self.report_location()?;
+        Ok(())
+    }
+
+    fn watched_cells(&self) -> Vec<Option<u32>> {
+        self.watches
+            .iter()
+            .map(|&cell| self.machine.tape().get(cell))
+            .collect()
+    }
+
+    fn report_location(&mut self) -> io::Result<()> {
+        if self.machine.is_finished() {
+            return writeln!(self.output, "the program has finished");
+        }
+
+        let instruction = self.machine.instruction();
+        writeln!(
+            self.output,
+            "{} at {} before '{}'",
+            if self.halted.is_some() {
+                "halted"
+            } else {
+                "stopped"
+            },
+            self.interpreter.spans()[instruction].start,
+            self.interpreter.program()[instruction],
+        )
+    }
+
+    fn resolve(&self, location: Location) -> Option<usize> {
+        self.interpreter
+            .spans()
+            .iter()
+            .position(|span| span.start >= location)
+    }
+
+    fn add_breakpoint(&mut self, location: Location) -> io::Result<()> {
+        match self.resolve(location) {
+            Some(instruction) => {
+                if !self.breakpoints.contains(&instruction) {
+                    self.breakpoints.push(instruction);
+                }
+                let start = self.interpreter.spans()[instruction].start;
+                writeln!(self.output, "breakpoint set at {}", start)
+            },
+            None => writeln!(self.output, "no instruction at or after {}", location),
+        }
+    }
+
+    fn delete_breakpoint(&mut self, location: Location) -> io::Result<()> {
+        match self.resolve(location) {
+            Some(instruction) if self.breakpoints.contains(&instruction) => {
+                self.breakpoints
+                    .retain(|&breakpoint| breakpoint != instruction);
+                let start = self.interpreter.spans()[instruction].start;
+                writeln!(self.output, "breakpoint deleted at {}", start)
+            },
+            _ => writeln!(self.output, "no breakpoint at or after {}", location),
+        }
+    }
+
+    fn print_tape(&mut self, radius: usize) -> io::Result<()> {
+        let head = self.machine.head();
+        let tape = self.machine.tape();
+        let start = head.saturating_sub(radius);
+        let end = head.saturating_add(radius).min(tape.len() - 1);
+        for cell in start..=end {
+            let marker = if cell == head { "->" } else { "  " };
+            writeln!(self.output, "{} cell {} = {}", marker, cell, tape[cell])?;
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, path: &str) -> io::Result<()> {
+        match fs::write(path, self.machine.save_snapshot()) {
+            Ok(()) => writeln!(self.output, "snapshot saved to {}", path),
+            Err(error) => writeln!(self.output, "unable to write {} ({})", path, error),
+        }
+    }
+
+    fn restore(&mut self, path: &str) -> io::Result<()> {
+        let snapshot = match fs::read(path) {
+            Ok(snapshot) => snapshot,
+            Err(error) => return writeln!(self.output, "unable to read {} ({})", path, error),
+        };
+        match Machine::load_snapshot(self.interpreter, &snapshot) {
+            Ok(machine) => {
+                self.machine = machine;
+                self.halted = None;
+                writeln!(self.output, "snapshot restored from {}", path)?;
+                self.report_location()
+            },
+            Err(error) => writeln!(self.output, "{}", error),
+        }
+    }
+}
+
+fn parse_location(argument: &str) -> Option<Location> {
+    let mut parts = argument.splitn(2, ':');
+    let line = parts.next()?.parse().ok()?;
+    let column = parts.next()?.parse().ok()?;
+    Some(Location::new(line, column))
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod debug_with {
+            use crate::{
+                configuration::{
+                    Configuration,
+                    TapeGrowth,
+                },
+                error::RuntimeError,
+                interpreter::Interpreter,
+            };
+            use std::{
+                env,
+                fs,
+                process,
+            };
+
+            fn debug(script: &[u8], session: &[u8]) -> String {
+                debug_with(Configuration::default(), script, session).unwrap()
+            }
+
+            fn debug_with(
+                configuration: Configuration,
+                script: &[u8],
+                session: &[u8],
+            ) -> Result<String, RuntimeError> {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                let mut output = Vec::new();
+                interpreter.debug_with(session, &mut output)?;
+                Ok(String::from_utf8(output).unwrap())
+            }
+
+            #[test]
+            fn step() {
+                let output = debug(b"++>\n+++.", b"step\ns 2\nstep\n");
+                assert_eq!(
+                    output,
+                    "stopped at 1:1 before '++'
+(bfdb) stopped at 1:3 before '>'
+(bfdb) stopped at 2:4 before '.'
+(bfdb) \u{3}the program has finished
+(bfdb) \n",
+                );
+            }
+
+            #[test]
+            fn breakpoints() {
+                let output = debug(
+                    b"+++[>+\n<-]>.",
+                    b"break 2:1\nb 9:9\ncontinue\nc\nd 2:1\ndelete 2:1\nc\nc\n",
+                );
+                assert_eq!(
+                    output,
+                    "stopped at 1:1 before '+++'
+(bfdb) breakpoint set at 2:1
+(bfdb) no instruction at or after 9:9
+(bfdb) hit a breakpoint
+stopped at 2:1 before '<'
+(bfdb) hit a breakpoint
+stopped at 2:1 before '<'
+(bfdb) breakpoint deleted at 2:1
+(bfdb) no breakpoint at or after 2:1
+(bfdb) \u{3}the program has finished
+(bfdb) the program has finished
+(bfdb) \n",
+                );
+            }
+
+            #[test]
+            fn watch() {
+                let output = debug(b"++>+++<[->+<]", b"watch 1\nc\nc\nunwatch 1\nc\nq\n");
+                assert_eq!(
+                    output,
+                    "stopped at 1:1 before '++'
+(bfdb) watching cell 1
+(bfdb) cell 1 changed from 0 to 3
+stopped at 1:7 before '<'
+(bfdb) cell 1 changed from 3 to 4
+stopped at 1:12 before '<'
+(bfdb) stopped watching cell 1
+(bfdb) the program has finished
+(bfdb) ",
+                );
+            }
+
+            #[test]
+            fn tape() {
+                let output = debug(b"+>++>+++<", b"s 6\ntape 1\nt 0\nt\nquit\n");
+                assert_eq!(
+                    output,
+                    "stopped at 1:1 before '+'
+(bfdb) the program has finished
+(bfdb)    cell 0 = 1
+-> cell 1 = 2
+   cell 2 = 3
+(bfdb) -> cell 1 = 2
+(bfdb)    cell 0 = 1
+-> cell 1 = 2
+   cell 2 = 3
+   cell 3 = 0
+   cell 4 = 0
+   cell 5 = 0
+   cell 6 = 0
+(bfdb) ",
+                );
+            }
+
+            #[test]
+            fn input() {
+                let output = debug(b",.,.", b"s 2\na\nc\nb");
+                assert_eq!(
+                    output,
+                    "stopped at 1:1 before ','
+(bfdb) astopped at 1:3 before ','
+(bfdb) (bfdb) bthe program has finished
+(bfdb) \n",
+                );
+            }
+
+            #[test]
+            fn embedded_input() {
+                let configuration = Configuration {
+                    extensions: true,
+                    ..Configuration::default()
+                };
+                let output = debug_with(configuration, b",.!a", b"c\n").unwrap();
+                assert_eq!(
+                    output,
+                    "stopped at 1:1 before ','
+(bfdb) athe program has finished
+(bfdb) \n",
+                );
+            }
+
+            #[test]
+            fn snapshots() {
+                let path = env::temp_dir().join(format!("bfdb-{}.snapshot", process::id()));
+                let path = path.to_str().unwrap();
+                let session = format!("s 2\nsave {0}\nc\nrestore {0}\ntape 1\nc\n", path);
+                let output = debug(b"+++>++.<.", session.as_bytes());
+                fs::remove_file(path).unwrap();
+                assert_eq!(
+                    output,
+                    format!(
+                        "stopped at 1:1 before '+++'
+(bfdb) stopped at 1:5 before '++'
+(bfdb) snapshot saved to {0}
+(bfdb) \u{2}\u{3}the program has finished
+(bfdb) snapshot restored from {0}
+stopped at 1:5 before '++'
+(bfdb)    cell 0 = 3
+-> cell 1 = 0
+   cell 2 = 0
+(bfdb) \u{2}\u{3}the program has finished
+(bfdb) \n",
+                        path,
+                    ),
+                );
+
+                let output = debug(b"+", b"restore /nonexistent/bfdb.snapshot\nrestore\nsave\n");
+                assert!(output.contains("unable to read /nonexistent/bfdb.snapshot"));
+                assert!(output.contains("usage: restore <file>"));
+                assert!(output.contains("usage: save <file>"));
+            }
+
+            #[test]
+            fn invalid_commands() {
+                let output = debug(b"+", b"\nfoo\nbreak\nstep x\nwatch\nhelp\n");
+                assert!(
+                    output.contains("unknown command 'foo', type 'help' for the list of commands")
+                );
+                assert!(output.contains("usage: break <line>:<column>"));
+                assert!(output.contains("usage: step [count]"));
+                assert!(output.contains("usage: watch <cell>"));
+                assert!(output.contains("commands:"));
+            }
+
+            #[test]
+            fn runtime_error() {
+                let configuration = Configuration {
+                    tape_growth: TapeGrowth::Fixed,
+                    ..Configuration::default()
+                };
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(b"+\n<+").unwrap();
+                let mut output = Vec::new();
+                let session = &b"continue\ntape 1\nstep\nquit\n"[..];
+                let error = interpreter.debug_with(session, &mut output).unwrap_err();
+                match error.without_location() {
+                    RuntimeError::CellUnderflow => {},
+                    _ => unreachable!(),
+                }
+
+                let output = String::from_utf8(output).unwrap();
+                assert!(output.contains("attempted to access a negative cell (at 2:1 in '<')\n"));
+                assert!(output.ends_with(
+                    "\
+halted at 2:1 before '<'
+(bfdb) -> cell 0 = 1
+   cell 1 = 0
+(bfdb) the program has halted
+(bfdb) ",
+                ));
+            }
+        }
+    }
+}
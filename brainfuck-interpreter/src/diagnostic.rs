@@ -0,0 +1,456 @@
+use crate::{
+    error::{
+        RuntimeError,
+        SyntaxError,
+    },
+    span::{
+        Location,
+        Span,
+    },
+};
+use colored::*;
+use std::fmt::Write;
+
+struct Annotation {
+    span: Span,
+    primary: bool,
+    label: String,
+}
+
+impl Annotation {
+    fn new(span: Span, primary: bool, label: String) -> Annotation {
+        Annotation {
+            span,
+            primary,
+            label,
+        }
+    }
+
+    fn paint(&self, text: &str) -> ColoredString {
+        if self.primary {
+            text.red().bold()
+        } else {
+            text.blue().bold()
+        }
+    }
+}
+
+fn lines(script: &[u8]) -> Vec<&[u8]> {
+    script
+        .split(|&byte| byte == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect()
+}
+
+// The location right after the last character of the script, ignoring the
+// trailing line breaks.
+fn end_of(script: &[u8]) -> Location {
+    let lines = lines(script);
+    let line = lines.iter().rposition(|line| !line.is_empty()).unwrap_or(0);
+    Location::new(line + 1, lines[line].len() + 1)
+}
+
+// Returns the padding before the markers, which keeps the tabs of the source
+// line to align with it, and the number of markers to draw under the annotated
+// part of the line. The columns of the spans are counted in bytes, so the line
+// is sliced by them before counting its characters.
+fn measure(line: &[u8], annotation: &Annotation) -> (String, usize) {
+    let start = annotation.span.start.column - 1;
+    let end = if annotation.span.end.line == annotation.span.start.line {
+        annotation.span.end.column
+    } else {
+        line.len()
+    };
+    // The columns past the end of the line (e.g., the end of the script) are a
+    // character each.
+    let before = &line[..start.min(line.len())];
+    let marked = &line[before.len()..end.clamp(before.len(), line.len())];
+    let padding = String::from_utf8_lossy(before)
+        .chars()
+        .map(|character| if character == '\t' { '\t' } else { ' ' })
+        .chain(std::iter::repeat(' ').take(start - before.len()))
+        .collect();
+    let length =
+        String::from_utf8_lossy(marked).chars().count() + end.saturating_sub(line.len().max(start));
+    (padding, length.max(1))
+}
+
+fn render(header: String, path: &str, script: &[u8], annotations: &mut [Annotation]) -> String {
+    annotations.sort_by_key(|annotation| annotation.span.start);
+
+    let lines = lines(script);
+    let width = annotations
+        .last()
+        .unwrap()
+        .span
+        .start
+        .line
+        .to_string()
+        .len();
+    let gutter = format!("{:width$} {}", "", "|".blue().bold(), width = width);
+
+    let mut rendered = String::new();
+    let primary = annotations
+        .iter()
+        .find(|annotation| annotation.primary)
+        .unwrap();
+    writeln!(rendered, "{}", header).unwrap();
+    writeln!(
+        rendered,
+        "{:width$}{} {}:{}",
+        "",
+        "-->".blue().bold(),
+        path,
+        primary.span.start,
+        width = width,
+    )
+    .unwrap();
+    write!(rendered, "{}", gutter).unwrap();
+
+    let mut previous_line = None;
+    let mut index = 0;
+    while index < annotations.len() {
+        let line_number = annotations[index].span.start.line;
+        let group = annotations[index..]
+            .iter()
+            .take_while(|annotation| annotation.span.start.line == line_number)
+            .count();
+        let group = &annotations[index..index + group];
+        index += group.len();
+
+        if let Some(previous_line) = previous_line {
+            if line_number > previous_line + 1 {
+                write!(rendered, "\n{}", "...".blue().bold()).unwrap();
+            }
+        }
+        previous_line = Some(line_number);
+
+        let source = lines.get(line_number - 1).copied().unwrap_or(b"");
+        let line = String::from_utf8_lossy(source);
+        write!(
+            rendered,
+            "\n{} {} {}",
+            format!("{:>width$}", line_number, width = width)
+                .blue()
+                .bold(),
+            "|".blue().bold(),
+            line,
+        )
+        .unwrap();
+
+        // Markers of the same line are drawn on a single row, with the label of the
+        // rightmost one next to it and the labels of the others below,
+        // connected with vertical bars.
+        let measured = group
+            .iter()
+            .map(|annotation| measure(source, annotation))
+            .collect::<Vec<_>>();
+        write!(rendered, "\n{} ", gutter).unwrap();
+        let mut drawn = 0;
+        for (annotation, (padding, length)) in group.iter().zip(measured.iter()) {
+            let marker = if annotation.primary { "^" } else { "-" };
+            write!(
+                rendered,
+                "{}{}",
+                padding.chars().skip(drawn).collect::<String>(),
+                annotation.paint(&marker.repeat(*length)),
+            )
+            .unwrap();
+            drawn = padding.chars().count() + length;
+        }
+        let last = group.last().unwrap();
+        write!(rendered, " {}", last.paint(&last.label)).unwrap();
+
+        for (position, annotation) in group.iter().enumerate().rev().skip(1) {
+            let bars = |until: usize| {
+                let mut row = String::new();
+                let mut drawn = 0;
+                for (other, (padding, _)) in group[..until].iter().zip(measured.iter()) {
+                    row.push_str(&padding.chars().skip(drawn).collect::<String>());
+                    row.push_str(&other.paint("|").to_string());
+                    drawn = padding.chars().count() + 1;
+                }
+                (row, drawn)
+            };
+            let (row, _) = bars(position + 1);
+            write!(rendered, "\n{} {}", gutter, row).unwrap();
+            let (row, drawn) = bars(position);
+            let padding = measured[position].0.chars().skip(drawn).collect::<String>();
+            write!(
+                rendered,
+                "\n{} {}{}{}",
+                gutter,
+                row,
+                padding,
+                annotation.paint(&annotation.label),
+            )
+            .unwrap();
+        }
+    }
+
+    rendered
+}
+
+impl SyntaxError {
+    pub fn render(&self, path: &str, script: &[u8]) -> String {
+        let header = format!("{} {}", "syntax error:".red().bold(), self.message());
+        let mut annotations = match *self {
+            SyntaxError::MissingOpeningBracket(line, column) => {
+                let location = Location::new(line, column);
+                vec![Annotation::new(
+                    Span::at(location),
+                    true,
+                    "unmatched ']'".to_owned(),
+                )]
+            },
+            SyntaxError::MissingClosingBracket(line, column) => {
+                let location = Location::new(line, column);
+                vec![
+                    Annotation::new(Span::at(location), true, "unmatched '['".to_owned()),
+                    Annotation::new(
+                        Span::at(end_of(script)),
+                        false,
+                        "the program ends here".to_owned(),
+                    ),
+                ]
+            },
+        };
+        render(header, path, script, &mut annotations)
+    }
+}
+
+impl RuntimeError {
+    pub fn render(&self, path: &str, script: &[u8]) -> String {
+        match self {
+            RuntimeError::Located {
+                error,
+                span,
+                instruction,
+                ..
+            } => {
+                let label = match **error {
+                    RuntimeError::FuelExhausted(_) | RuntimeError::TimedOut(_) => {
+                        format!("stopped before '{}'", instruction)
+                    },
+                    _ => format!("while executing '{}'", instruction),
+                };
+                let mut annotations = vec![Annotation::new(*span, true, label)];
+                render(error.to_string(), path, script, &mut annotations)
+            },
+            _ => self.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+fn plain(rendered: &str) -> String {
+    let mut result = String::new();
+    let mut characters = rendered.chars();
+    while let Some(character) = characters.next() {
+        if character == '\u{1b}' {
+            characters.by_ref().find(|&character| character == 'm');
+        } else {
+            result.push(character);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod syntax_error {
+    mod methods {
+        mod render {
+            use crate::{
+                diagnostic::plain,
+                error::SyntaxError,
+                interpreter::Interpreter,
+            };
+
+            #[test]
+            fn missing_opening_bracket() {
+                let error = SyntaxError::MissingOpeningBracket(2, 4);
+                assert_eq!(
+                    plain(&error.render("a.bf", b"+++\n[-]]+\n")),
+                    [
+                        "syntax error: unable to find the opening bracket of ']'",
+                        " --> a.bf:2:4",
+                        "  |",
+                        "2 | [-]]+",
+                        "  |    ^ unmatched ']'",
+                    ]
+                    .join("\n"),
+                );
+            }
+
+            #[test]
+            fn missing_closing_bracket() {
+                let error = SyntaxError::MissingClosingBracket(1, 2);
+                assert_eq!(
+                    plain(&error.render("a.bf", b"+[-\n\n>\n<\r\n\n")),
+                    [
+                        "syntax error: unable to find the closing bracket of '['",
+                        " --> a.bf:1:2",
+                        "  |",
+                        "1 | +[-",
+                        "  |  ^ unmatched '['",
+                        "...",
+                        "4 | <",
+                        "  |  - the program ends here",
+                    ]
+                    .join("\n"),
+                );
+
+                let error = SyntaxError::MissingClosingBracket(1, 2);
+                assert_eq!(
+                    plain(&error.render("a.bf", b"+[-\n>")),
+                    [
+                        "syntax error: unable to find the closing bracket of '['",
+                        " --> a.bf:1:2",
+                        "  |",
+                        "1 | +[-",
+                        "  |  ^ unmatched '['",
+                        "2 | >",
+                        "  |  - the program ends here",
+                    ]
+                    .join("\n"),
+                );
+            }
+
+            #[test]
+            fn markers_on_the_same_line() {
+                let error = SyntaxError::MissingClosingBracket(1, 2);
+                assert_eq!(
+                    plain(&error.render("a.bf", b"+[->+<")),
+                    [
+                        "syntax error: unable to find the closing bracket of '['",
+                        " --> a.bf:1:2",
+                        "  |",
+                        "1 | +[->+<",
+                        "  |  ^    - the program ends here",
+                        "  |  |",
+                        "  |  unmatched '['",
+                    ]
+                    .join("\n"),
+                );
+            }
+
+            #[test]
+            fn tabs_and_wide_gutters() {
+                let error = SyntaxError::MissingOpeningBracket(10, 3);
+                let script = format!("{}\t+]", "\n".repeat(9));
+                assert_eq!(
+                    plain(&error.render("a.bf", script.as_bytes())),
+                    [
+                        "syntax error: unable to find the opening bracket of ']'",
+                        "  --> a.bf:10:3",
+                        "   |",
+                        "10 | \t+]",
+                        "   | \t ^ unmatched ']'",
+                    ]
+                    .join("\n"),
+                );
+            }
+
+            #[test]
+            fn non_ascii_comments() {
+                let mut interpreter = Interpreter::new();
+                let script = "héllo wörld ]".as_bytes();
+                let error = interpreter.load(script).unwrap_err();
+                assert_eq!(
+                    plain(&error.render("a.bf", script)),
+                    [
+                        "syntax error: unable to find the opening bracket of ']'",
+                        " --> a.bf:1:15",
+                        "  |",
+                        "1 | héllo wörld ]",
+                        "  |             ^ unmatched ']'",
+                    ]
+                    .join("\n"),
+                );
+
+                let script = "ü [+ ö".as_bytes();
+                let error = interpreter.load(script).unwrap_err();
+                assert_eq!(
+                    plain(&error.render("a.bf", script)),
+                    [
+                        "syntax error: unable to find the closing bracket of '['",
+                        " --> a.bf:1:4",
+                        "  |",
+                        "1 | ü [+ ö",
+                        "  |   ^   - the program ends here",
+                        "  |   |",
+                        "  |   unmatched '['",
+                    ]
+                    .join("\n"),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod runtime_error {
+    mod methods {
+        mod render {
+            use crate::{
+                diagnostic::plain,
+                error::RuntimeError,
+                instruction::Instruction,
+                span::{
+                    Location,
+                    Span,
+                },
+            };
+
+            #[test]
+            fn located() {
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::CellUnderflow),
+                    span: Span::new(Location::new(2, 2), Location::new(2, 4)),
+                    instruction: Instruction::MoveLeft(3),
+                    index: 0,
+                    head: 0,
+                };
+                assert_eq!(
+                    plain(&error.render("a.bf", b"+\n-<<<\n")),
+                    [
+                        "runtime error: attempted to access a negative cell",
+                        " --> a.bf:2:2",
+                        "  |",
+                        "2 | -<<<",
+                        "  |  ^^^ while executing '<<<'",
+                    ]
+                    .join("\n"),
+                );
+            }
+
+            #[test]
+            fn limits() {
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::FuelExhausted(10)),
+                    span: Span::at(Location::new(1, 3)),
+                    instruction: Instruction::EndLoop(0),
+                    index: 1,
+                    head: 0,
+                };
+                assert_eq!(
+                    plain(&error.render("a.bf", b"[+]")),
+                    [
+                        "runtime error: ran out of steps after executing 10 instructions",
+                        " --> a.bf:1:3",
+                        "  |",
+                        "1 | [+]",
+                        "  |   ^ stopped before ']'",
+                    ]
+                    .join("\n"),
+                );
+            }
+
+            #[test]
+            fn unlocated() {
+                let error = RuntimeError::CellOverflow;
+                assert_eq!(error.render("a.bf", b">"), error.to_string());
+            }
+        }
+    }
+}
@@ -1,3 +1,10 @@
+use crate::{
+    instruction::Instruction,
+    span::{
+        Location,
+        Span,
+    },
+};
 use colored::*;
 use std::{
     error::Error,
@@ -13,41 +20,73 @@ pub enum RuntimeError {
     Io(io::Error),
     CellUnderflow,
     CellOverflow,
+    ValueUnderflow,
+    ValueOverflow,
+    FuelExhausted(u64),
+    TimedOut(u64),
+    // The index of the instruction, which failed, and the position of the head at that time.
+    Located {
+        error: Box<RuntimeError>,
+        span: Span,
+        instruction: Instruction,
+        index: usize,
+        head: usize,
+    },
 }
 
-impl Display for RuntimeError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl RuntimeError {
+    pub fn without_location(&self) -> &RuntimeError {
         match *self {
-            RuntimeError::Io(ref error) => {
-                write!(
-                    f,
-                    "{} unexpected io error occurred ({})",
-                    "runtime error:".red().bold(),
-                    error,
-                )
+            RuntimeError::Located { ref error, .. } => error.without_location(),
+            _ => self,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match *self {
+            RuntimeError::Io(ref error) => format!("unexpected io error occurred ({})", error),
+            RuntimeError::CellUnderflow => "attempted to access a negative cell".to_owned(),
+            RuntimeError::CellOverflow => {
+                "attempted to access a cell, which is above the cell limit".to_owned()
             },
-            RuntimeError::CellUnderflow => {
-                write!(
-                    f,
-                    "{} attempted to access a negative cell",
-                    "runtime error:".red().bold(),
-                )
+            RuntimeError::ValueUnderflow => "attempted to decrement a cell below zero".to_owned(),
+            RuntimeError::ValueOverflow => {
+                "attempted to increment a cell above its maximum value".to_owned()
             },
-            RuntimeError::CellOverflow => {
-                write!(
-                    f,
-                    "{} attempted to access a cell, which is above the cell limit",
-                    "runtime error:".red().bold(),
+            RuntimeError::FuelExhausted(steps) => {
+                format!("ran out of steps after executing {} instructions", steps)
+            },
+            RuntimeError::TimedOut(steps) => {
+                format!("timed out after executing {} instructions", steps)
+            },
+            RuntimeError::Located {
+                ref error,
+                span,
+                ref instruction,
+                ..
+            } => {
+                format!(
+                    "{} (at {} in '{}')",
+                    error.message(),
+                    span.start,
+                    instruction
                 )
             },
         }
     }
 }
 
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", "runtime error:".red().bold(), self.message())
+    }
+}
+
 impl Error for RuntimeError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             RuntimeError::Io(err) => Some(err),
+            RuntimeError::Located { error, .. } => error.source(),
             _ => None,
         }
     }
@@ -62,9 +101,49 @@ impl From<io::Error> for RuntimeError {
 
 #[cfg(test)]
 mod runtime_error {
+    mod methods {
+        mod without_location {
+            use crate::{
+                error::RuntimeError,
+                instruction::Instruction,
+                span::{
+                    Location,
+                    Span,
+                },
+            };
+
+            #[test]
+            fn without_location() {
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::ValueOverflow),
+                    span: Span::at(Location::new(1, 4)),
+                    instruction: Instruction::Increment(1),
+                    index: 0,
+                    head: 0,
+                };
+                match error.without_location() {
+                    RuntimeError::ValueOverflow => {},
+                    _ => unreachable!(),
+                }
+
+                match RuntimeError::CellOverflow.without_location() {
+                    RuntimeError::CellOverflow => {},
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+
     mod traits {
         mod display {
-            use crate::error::RuntimeError;
+            use crate::{
+                error::RuntimeError,
+                instruction::Instruction,
+                span::{
+                    Location,
+                    Span,
+                },
+            };
             use colored::*;
             use std::{
                 error::Error,
@@ -118,10 +197,84 @@ mod runtime_error {
                     ),
                 );
             }
+
+            #[test]
+            fn value_underflow() {
+                let error = RuntimeError::ValueUnderflow;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} attempted to decrement a cell below zero",
+                        "runtime error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn value_overflow() {
+                let error = RuntimeError::ValueOverflow;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} attempted to increment a cell above its maximum value",
+                        "runtime error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn fuel_exhausted() {
+                let error = RuntimeError::FuelExhausted(1_000);
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} ran out of steps after executing 1000 instructions",
+                        "runtime error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn timed_out() {
+                let error = RuntimeError::TimedOut(42);
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} timed out after executing 42 instructions",
+                        "runtime error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn located() {
+                let span = Span::new(Location::new(2, 3), Location::new(2, 4));
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::CellUnderflow),
+                    span,
+                    instruction: Instruction::MoveLeft(2),
+                    index: 0,
+                    head: 0,
+                };
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} attempted to access a negative cell (at 2:3 in '<<')",
+                        "runtime error:".red().bold(),
+                    ),
+                );
+            }
         }
 
         mod error {
-            use crate::error::RuntimeError;
+            use crate::{
+                error::RuntimeError,
+                instruction::Instruction,
+                span::{
+                    Location,
+                    Span,
+                },
+            };
             use std::{
                 error::Error,
                 io,
@@ -156,6 +309,43 @@ mod runtime_error {
                 let error = RuntimeError::CellOverflow;
                 assert!(error.source().is_none());
             }
+
+            #[test]
+            fn source_on_value_underflow() {
+                let error = RuntimeError::ValueUnderflow;
+                assert!(error.source().is_none());
+            }
+
+            #[test]
+            fn source_on_value_overflow() {
+                let error = RuntimeError::ValueOverflow;
+                assert!(error.source().is_none());
+            }
+
+            #[test]
+            fn source_on_located() {
+                let span = Span::at(Location::new(1, 1));
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::Io(io::ErrorKind::NotFound.into())),
+                    span,
+                    instruction: Instruction::Write,
+                    index: 0,
+                    head: 0,
+                };
+                assert_eq!(
+                    format!("{}", error.source().unwrap()),
+                    format!("{}", io::Error::from(io::ErrorKind::NotFound)),
+                );
+
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::CellOverflow),
+                    span,
+                    instruction: Instruction::MoveRight(1),
+                    index: 0,
+                    head: 0,
+                };
+                assert!(error.source().is_none());
+            }
         }
 
         mod from {
@@ -189,29 +379,38 @@ mod runtime_error {
 }
 
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum SyntaxError {
     MissingOpeningBracket(usize, usize),
     MissingClosingBracket(usize, usize),
 }
 
+impl SyntaxError {
+    pub fn location(&self) -> Location {
+        match *self {
+            SyntaxError::MissingOpeningBracket(line, column) |
+            SyntaxError::MissingClosingBracket(line, column) => Location::new(line, column),
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match *self {
+            SyntaxError::MissingOpeningBracket(_, _) => "unable to find the opening bracket of ']'",
+            SyntaxError::MissingClosingBracket(_, _) => "unable to find the closing bracket of '['",
+        }
+    }
+}
+
 impl Display for SyntaxError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            SyntaxError::MissingOpeningBracket(line, column) => {
-                write!(
-                    f,
-                    "{} unable to find the opening bracket of ']' at {}:{}",
-                    "syntax error:".red().bold(),
-                    line,
-                    column,
-                )
-            },
+            SyntaxError::MissingOpeningBracket(line, column) |
             SyntaxError::MissingClosingBracket(line, column) => {
                 write!(
                     f,
-                    "{} unable to find the closing bracket of '[' at {}:{}",
+                    "{} {} at {}:{}",
                     "syntax error:".red().bold(),
+                    self.message(),
                     line,
                     column,
                 )
@@ -224,6 +423,27 @@ impl Error for SyntaxError {}
 
 #[cfg(test)]
 mod syntax_error {
+    mod methods {
+        mod location {
+            use crate::{
+                error::SyntaxError,
+                span::Location,
+            };
+
+            #[test]
+            fn location() {
+                assert_eq!(
+                    SyntaxError::MissingOpeningBracket(3, 14).location(),
+                    Location::new(3, 14),
+                );
+                assert_eq!(
+                    SyntaxError::MissingClosingBracket(1, 5).location(),
+                    Location::new(1, 5),
+                );
+            }
+        }
+    }
+
     mod traits {
         mod display {
             use crate::error::SyntaxError;
@@ -267,3 +487,380 @@ mod syntax_error {
         }
     }
 }
+
+
+#[derive(Debug)]
+pub enum BytecodeError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    SourceMismatch,
+    ConfigurationMismatch,
+    Malformed,
+}
+
+impl Display for BytecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BytecodeError::InvalidMagic => {
+                write!(
+                    f,
+                    "{} the data is not brainfuck bytecode",
+                    "bytecode error:".red().bold()
+                )
+            },
+            BytecodeError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "{} unsupported bytecode version {}",
+                    "bytecode error:".red().bold(),
+                    version,
+                )
+            },
+            BytecodeError::ChecksumMismatch => {
+                write!(
+                    f,
+                    "{} the checksum of the bytecode does not match its contents",
+                    "bytecode error:".red().bold(),
+                )
+            },
+            BytecodeError::SourceMismatch => {
+                write!(
+                    f,
+                    "{} the bytecode was compiled from a different script",
+                    "bytecode error:".red().bold(),
+                )
+            },
+            BytecodeError::ConfigurationMismatch => {
+                write!(
+                    f,
+                    "{} the bytecode was compiled with a different configuration",
+                    "bytecode error:".red().bold(),
+                )
+            },
+            BytecodeError::Malformed => {
+                write!(
+                    f,
+                    "{} the bytecode is malformed",
+                    "bytecode error:".red().bold()
+                )
+            },
+        }
+    }
+}
+
+impl Error for BytecodeError {}
+
+#[cfg(test)]
+mod bytecode_error {
+    mod traits {
+        mod display {
+            use crate::error::BytecodeError;
+            use colored::*;
+
+            #[test]
+            fn invalid_magic() {
+                let error = BytecodeError::InvalidMagic;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the data is not brainfuck bytecode",
+                        "bytecode error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn unsupported_version() {
+                for version in 2..=10 {
+                    let error = BytecodeError::UnsupportedVersion(version);
+                    assert_eq!(
+                        format!("{}", error),
+                        format!(
+                            "{} unsupported bytecode version {}",
+                            "bytecode error:".red().bold(),
+                            version,
+                        ),
+                    );
+                }
+            }
+
+            #[test]
+            fn checksum_mismatch() {
+                let error = BytecodeError::ChecksumMismatch;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the checksum of the bytecode does not match its contents",
+                        "bytecode error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn source_mismatch() {
+                let error = BytecodeError::SourceMismatch;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the bytecode was compiled from a different script",
+                        "bytecode error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn configuration_mismatch() {
+                let error = BytecodeError::ConfigurationMismatch;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the bytecode was compiled with a different configuration",
+                        "bytecode error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn malformed() {
+                let error = BytecodeError::Malformed;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the bytecode is malformed",
+                        "bytecode error:".red().bold()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    ChecksumMismatch,
+    ProgramMismatch,
+    ConfigurationMismatch,
+    Malformed,
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SnapshotError::InvalidMagic => {
+                write!(
+                    f,
+                    "{} the data is not a brainfuck snapshot",
+                    "snapshot error:".red().bold()
+                )
+            },
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "{} unsupported snapshot version {}",
+                    "snapshot error:".red().bold(),
+                    version,
+                )
+            },
+            SnapshotError::ChecksumMismatch => {
+                write!(
+                    f,
+                    "{} the checksum of the snapshot does not match its contents",
+                    "snapshot error:".red().bold(),
+                )
+            },
+            SnapshotError::ProgramMismatch => {
+                write!(
+                    f,
+                    "{} the snapshot was taken from a different program",
+                    "snapshot error:".red().bold(),
+                )
+            },
+            SnapshotError::ConfigurationMismatch => {
+                write!(
+                    f,
+                    "{} the snapshot was taken with a different configuration",
+                    "snapshot error:".red().bold(),
+                )
+            },
+            SnapshotError::Malformed => {
+                write!(
+                    f,
+                    "{} the snapshot is malformed",
+                    "snapshot error:".red().bold()
+                )
+            },
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+#[cfg(test)]
+mod snapshot_error {
+    mod traits {
+        mod display {
+            use crate::error::SnapshotError;
+            use colored::*;
+
+            #[test]
+            fn invalid_magic() {
+                let error = SnapshotError::InvalidMagic;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the data is not a brainfuck snapshot",
+                        "snapshot error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn unsupported_version() {
+                for version in 2..=10 {
+                    let error = SnapshotError::UnsupportedVersion(version);
+                    assert_eq!(
+                        format!("{}", error),
+                        format!(
+                            "{} unsupported snapshot version {}",
+                            "snapshot error:".red().bold(),
+                            version,
+                        ),
+                    );
+                }
+            }
+
+            #[test]
+            fn checksum_mismatch() {
+                let error = SnapshotError::ChecksumMismatch;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the checksum of the snapshot does not match its contents",
+                        "snapshot error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn program_mismatch() {
+                let error = SnapshotError::ProgramMismatch;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the snapshot was taken from a different program",
+                        "snapshot error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn configuration_mismatch() {
+                let error = SnapshotError::ConfigurationMismatch;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the snapshot was taken with a different configuration",
+                        "snapshot error:".red().bold(),
+                    ),
+                );
+            }
+
+            #[test]
+            fn malformed() {
+                let error = SnapshotError::Malformed;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the snapshot is malformed",
+                        "snapshot error:".red().bold()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum TraceError {
+    InvalidMagic,
+    UnsupportedVersion(u16),
+    Malformed,
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TraceError::InvalidMagic => {
+                write!(
+                    f,
+                    "{} the data is not a brainfuck trace",
+                    "trace error:".red().bold()
+                )
+            },
+            TraceError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "{} unsupported trace version {}",
+                    "trace error:".red().bold(),
+                    version
+                )
+            },
+            TraceError::Malformed => {
+                write!(f, "{} the trace is malformed", "trace error:".red().bold())
+            },
+        }
+    }
+}
+
+impl Error for TraceError {}
+
+#[cfg(test)]
+mod trace_error {
+    mod traits {
+        mod display {
+            use crate::error::TraceError;
+            use colored::*;
+
+            #[test]
+            fn invalid_magic() {
+                let error = TraceError::InvalidMagic;
+                assert_eq!(
+                    format!("{}", error),
+                    format!(
+                        "{} the data is not a brainfuck trace",
+                        "trace error:".red().bold()
+                    ),
+                );
+            }
+
+            #[test]
+            fn unsupported_version() {
+                for version in 2..=10 {
+                    let error = TraceError::UnsupportedVersion(version);
+                    assert_eq!(
+                        format!("{}", error),
+                        format!(
+                            "{} unsupported trace version {}",
+                            "trace error:".red().bold(),
+                            version,
+                        ),
+                    );
+                }
+            }
+
+            #[test]
+            fn malformed() {
+                let error = TraceError::Malformed;
+                assert_eq!(
+                    format!("{}", error),
+                    format!("{} the trace is malformed", "trace error:".red().bold()),
+                );
+            }
+        }
+    }
+}
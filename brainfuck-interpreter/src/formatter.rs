@@ -0,0 +1,405 @@
+use crate::interpreter::Interpreter;
+
+// Each loop nesting level is indented with this many spaces.
+const INDENTATION: usize = 4;
+
+// Loops up to this long, which only contain commands, are kept on a single line
+// (e.g., '[-]').
+const INLINE_LOOP_LENGTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FormatStyle {
+    Pretty,
+    Minified,
+}
+
+impl Default for FormatStyle {
+    #[inline]
+    fn default() -> FormatStyle {
+        FormatStyle::Pretty
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct FormatOptions {
+    pub style: FormatStyle,
+    pub line_width: usize,
+    pub keep_comments: bool,
+}
+
+impl Default for FormatOptions {
+    #[inline]
+    fn default() -> FormatOptions {
+        FormatOptions {
+            style: FormatStyle::default(),
+            line_width: 80,
+            keep_comments: false,
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Token<'s> {
+    Command(u8),
+    Comment(&'s [u8]),
+}
+
+impl Interpreter {
+    // Formats the script rather than the loaded program, so the result doesn't
+    // depend on the optimizations, and the comments can be kept. The embedded
+    // input is kept as is, and the brackets are expected to be balanced (i.e.,
+    // the script is expected to be loaded before).
+    pub fn format(&self, script: &[u8], options: &FormatOptions) -> Vec<u8> {
+        let (program, embedded_input) = self.split_script(script);
+        let tokens = self.tokenize(program, options.keep_comments);
+
+        let mut formatted = match options.style {
+            FormatStyle::Pretty => pretty(&tokens, options.line_width),
+            FormatStyle::Minified => minify(&tokens, embedded_input.is_none()),
+        };
+        if let Some(embedded_input) = embedded_input {
+            formatted.push(b'!');
+            formatted.extend_from_slice(embedded_input);
+        }
+        formatted
+    }
+
+    // Comments, which consist of whitespace only, are dropped even if the comments
+    // are kept, as the formatter is the one laying out the commands.
+    fn tokenize<'s>(&self, program: &'s [u8], keep_comments: bool) -> Vec<Token<'s>> {
+        let is_command = |token: u8| {
+            matches!(token, b'+' | b'-' | b'>' | b'<' | b'.' | b',' | b'[' | b']') ||
+                (token == b'#' && self.configuration().extensions)
+        };
+
+        let mut tokens = Vec::new();
+        let mut start = 0;
+        while start < program.len() {
+            if is_command(program[start]) {
+                tokens.push(Token::Command(program[start]));
+                start += 1;
+                continue;
+            }
+
+            let end = program[start..]
+                .iter()
+                .position(|&token| is_command(token))
+                .map_or(program.len(), |length| start + length);
+            let comment = &program[start..end];
+            if keep_comments && !trim(comment).is_empty() {
+                tokens.push(Token::Comment(comment));
+            }
+            start = end;
+        }
+        tokens
+    }
+}
+
+fn trim(text: &[u8]) -> &[u8] {
+    let start = text
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let end = text
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(start, |last| last + 1);
+    &text[start..end]
+}
+
+fn commands(tokens: &[Token]) -> Vec<u8> {
+    tokens
+        .iter()
+        .filter_map(|token| {
+            match *token {
+                Token::Command(command) => Some(command),
+                Token::Comment(_) => None,
+            }
+        })
+        .collect()
+}
+
+fn minify(tokens: &[Token], newline: bool) -> Vec<u8> {
+    let mut minified = commands(tokens);
+    if newline && !minified.is_empty() {
+        minified.push(b'\n');
+    }
+    minified
+}
+
+// Loops are laid out with their brackets on separate lines and their bodies
+// indented, unless they are short enough to be inlined. Commands in between are
+// wrapped at the line width, and each line of the comments is put on its own
+// line.
+fn pretty(tokens: &[Token], line_width: usize) -> Vec<u8> {
+    let mut printer = Printer {
+        output: Vec::new(),
+        line: Vec::new(),
+        depth: 0,
+        line_width,
+    };
+
+    let mut index = 0;
+    while index < tokens.len() {
+        match tokens[index] {
+            Token::Comment(comment) => {
+                for line in comment.split(|&token| token == b'\n') {
+                    let line = trim(line);
+                    if !line.is_empty() {
+                        printer.push_line(line);
+                    }
+                }
+            },
+            Token::Command(b'[') => {
+                if let Some(end) = inline_loop(tokens, index) {
+                    let code = commands(&tokens[index..=end]);
+                    if code.len() <= INLINE_LOOP_LENGTH.min(printer.width()) {
+                        printer.push_code(&code);
+                        index = end + 1;
+                        continue;
+                    }
+                }
+                printer.push_line(b"[");
+                printer.depth += 1;
+            },
+            Token::Command(b']') => {
+                printer.flush();
+                printer.depth = printer.depth.saturating_sub(1);
+                printer.push_line(b"]");
+            },
+            Token::Command(command) => printer.push_code(&[command]),
+        }
+        index += 1;
+    }
+    printer.flush();
+
+    printer.output
+}
+
+// Returns the index of the closing bracket of the loop starting at the index,
+// if there are no nested loops or comments in it.
+fn inline_loop(tokens: &[Token], start: usize) -> Option<usize> {
+    for (index, token) in tokens.iter().enumerate().skip(start + 1) {
+        match *token {
+            Token::Command(b']') => return Some(index),
+            Token::Command(b'[') | Token::Comment(_) => return None,
+            Token::Command(_) => {},
+        }
+    }
+    None
+}
+
+struct Printer {
+    output: Vec<u8>,
+    line: Vec<u8>,
+    depth: usize,
+    line_width: usize,
+}
+
+impl Printer {
+    // At least one command is put on each line, even if the indentation exceeds the
+    // line width.
+    fn width(&self) -> usize {
+        self.line_width
+            .saturating_sub(self.depth * INDENTATION)
+            .max(1)
+    }
+
+    fn push_code(&mut self, code: &[u8]) {
+        if !self.line.is_empty() && self.line.len() + code.len() > self.width() {
+            self.flush();
+        }
+        self.line.extend_from_slice(code);
+    }
+
+    fn push_line(&mut self, line: &[u8]) {
+        self.flush();
+        self.output
+            .resize(self.output.len() + self.depth * INDENTATION, b' ');
+        self.output.extend_from_slice(line);
+        self.output.push(b'\n');
+    }
+
+    fn flush(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.push_line(&line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod format {
+            use crate::{
+                configuration::Configuration,
+                formatter::{
+                    FormatOptions,
+                    FormatStyle,
+                },
+                interpreter::Interpreter,
+            };
+
+            fn format(
+                configuration: Configuration,
+                script: &[u8],
+                options: FormatOptions,
+            ) -> String {
+                let interpreter = Interpreter::with_configuration(configuration);
+                String::from_utf8(interpreter.format(script, &options)).unwrap()
+            }
+
+            #[test]
+            fn minified() {
+                let options = FormatOptions {
+                    style: FormatStyle::Minified,
+                    ..FormatOptions::default()
+                };
+                let script = b"set cell 0 to 2: ++\n[ move it: ->+< ] print: >.";
+                assert_eq!(
+                    format(Configuration::default(), script, options),
+                    "++[->+<]>.\n"
+                );
+            }
+
+            #[test]
+            fn pretty() {
+                let script = b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.";
+                assert_eq!(
+                    format(Configuration::default(), script, FormatOptions::default()),
+                    "\
+++++++++
+[
+    >++++
+    [
+        >++>+++>+++>+<<<<-
+    ]
+    >+>+>->>+[<]<-
+]
+>>.
+",
+                );
+            }
+
+            #[test]
+            fn line_width() {
+                let options = FormatOptions {
+                    line_width: 8,
+                    ..FormatOptions::default()
+                };
+                let script = b"++++++++++[>+++++[-]>>>>>+<<<<<<-]";
+                assert_eq!(
+                    format(Configuration::default(), script, options),
+                    "\
+++++++++
+++
+[
+    >+++
+    ++
+    [-]>
+    >>>>
+    +<<<
+    <<<-
+]
+",
+                );
+            }
+
+            #[test]
+            fn inline_loops() {
+                let script = b"+[->+<]>[->>>+<<<]";
+                assert_eq!(
+                    format(Configuration::default(), script, FormatOptions::default()),
+                    "+[->+<]>\n[\n    ->>>+<<<\n]\n",
+                );
+
+                let options = FormatOptions {
+                    line_width: 4,
+                    ..FormatOptions::default()
+                };
+                assert_eq!(
+                    format(Configuration::default(), b"+[->+<]", options),
+                    "+\n[\n    -\n    >\n    +\n    <\n]\n",
+                );
+            }
+
+            #[test]
+            fn comments() {
+                let options = FormatOptions {
+                    keep_comments: true,
+                    ..FormatOptions::default()
+                };
+                let script = b"  set cell 0 to 2: ++ \n\n[ move it\n  to cell 1: ->+<]  >. done\n";
+                assert_eq!(
+                    format(Configuration::default(), script, options),
+                    "\
+set cell 0 to 2:
+++
+[
+    move it
+    to cell 1:
+    ->+<
+]
+>.
+done
+",
+                );
+            }
+
+            #[test]
+            fn extensions() {
+                let configuration = Configuration {
+                    extensions: true,
+                    ..Configuration::default()
+                };
+                let script = b"+[-]# dump ,. !in  put";
+                assert_eq!(
+                    format(configuration.clone(), script, FormatOptions::default()),
+                    "+[-]#,.\n!in  put",
+                );
+                let options = FormatOptions {
+                    style: FormatStyle::Minified,
+                    ..FormatOptions::default()
+                };
+                assert_eq!(format(configuration, script, options), "+[-]#,.!in  put");
+
+                assert_eq!(
+                    format(Configuration::default(), script, FormatOptions::default()),
+                    "+[-],.\n",
+                );
+            }
+
+            #[test]
+            fn idempotent() {
+                let options = FormatOptions {
+                    line_width: 12,
+                    keep_comments: true,
+                    ..FormatOptions::default()
+                };
+                let script = b"a++++++++[>++++ b [>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.c\n";
+                let formatted = format(Configuration::default(), script, options.clone());
+                let reformatted = format(Configuration::default(), formatted.as_bytes(), options);
+                assert_eq!(formatted, reformatted);
+            }
+
+            #[test]
+            fn empty() {
+                assert_eq!(
+                    format(Configuration::default(), b"", FormatOptions::default()),
+                    ""
+                );
+                assert_eq!(
+                    format(
+                        Configuration::default(),
+                        b"comment",
+                        FormatOptions::default()
+                    ),
+                    "",
+                );
+            }
+        }
+    }
+}
@@ -7,12 +7,41 @@ use std::fmt::{
 pub enum Instruction {
     MoveLeft(usize),
     MoveRight(usize),
-    Increment(u8),
-    Decrement(u8),
+    Increment(usize),
+    Decrement(usize),
     Read,
     Write,
     StartLoop(usize),
     EndLoop(usize),
+    SetZero,
+    ScanLeft(usize),
+    ScanRight(usize),
+    MultiplyAdd(Vec<(isize, isize)>),
+    IncrementAt(isize, usize),
+    DecrementAt(isize, usize),
+    ReadAt(isize),
+    WriteAt(isize),
+    Dump,
+}
+
+impl Instruction {
+    #[inline]
+    pub(crate) fn movement(offset: isize) -> Instruction {
+        if offset < 0 {
+            Instruction::MoveLeft(offset.unsigned_abs())
+        } else {
+            Instruction::MoveRight(offset as usize)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn arithmetic(amount: isize) -> Instruction {
+        if amount < 0 {
+            Instruction::Decrement(amount.unsigned_abs())
+        } else {
+            Instruction::Increment(amount as usize)
+        }
+    }
 }
 
 impl Display for Instruction {
@@ -46,6 +75,54 @@ impl Display for Instruction {
             Instruction::Write => write!(f, "."),
             Instruction::StartLoop(_) => write!(f, "["),
             Instruction::EndLoop(_) => write!(f, "]"),
+            Instruction::SetZero => write!(f, "[-]"),
+            Instruction::ScanLeft(amount) => write!(f, "[{}]", Instruction::MoveLeft(amount)),
+            Instruction::ScanRight(amount) => write!(f, "[{}]", Instruction::MoveRight(amount)),
+            Instruction::MultiplyAdd(ref targets) => {
+                write!(f, "[-")?;
+                let mut position = 0;
+                for &(offset, factor) in targets {
+                    write!(f, "{}", Instruction::movement(offset - position))?;
+                    write!(f, "{}", Instruction::arithmetic(factor))?;
+                    position = offset;
+                }
+                write!(f, "{}]", Instruction::movement(-position))
+            },
+            Instruction::IncrementAt(offset, amount) => {
+                write!(
+                    f,
+                    "{}{}{}",
+                    Instruction::movement(offset),
+                    Instruction::Increment(amount),
+                    Instruction::movement(-offset),
+                )
+            },
+            Instruction::DecrementAt(offset, amount) => {
+                write!(
+                    f,
+                    "{}{}{}",
+                    Instruction::movement(offset),
+                    Instruction::Decrement(amount),
+                    Instruction::movement(-offset),
+                )
+            },
+            Instruction::ReadAt(offset) => {
+                write!(
+                    f,
+                    "{},{}",
+                    Instruction::movement(offset),
+                    Instruction::movement(-offset)
+                )
+            },
+            Instruction::WriteAt(offset) => {
+                write!(
+                    f,
+                    "{}.{}",
+                    Instruction::movement(offset),
+                    Instruction::movement(-offset)
+                )
+            },
+            Instruction::Dump => write!(f, "#"),
         }
     }
 }
@@ -175,6 +252,90 @@ mod instruction {
                 let instruction = Instruction::EndLoop(3);
                 assert_eq!(format!("{}", instruction), "]");
             }
+
+            #[test]
+            fn set_zero() {
+                let instruction = Instruction::SetZero;
+                assert_eq!(format!("{}", instruction), "[-]");
+            }
+
+            #[test]
+            fn scan_left_with_one() {
+                let instruction = Instruction::ScanLeft(1);
+                assert_eq!(format!("{}", instruction), "[<]");
+            }
+
+            #[test]
+            fn scan_left_with_more_than_one() {
+                let instruction = Instruction::ScanLeft(3);
+                assert_eq!(format!("{}", instruction), "[<<<]");
+            }
+
+            #[test]
+            fn scan_right_with_one() {
+                let instruction = Instruction::ScanRight(1);
+                assert_eq!(format!("{}", instruction), "[>]");
+            }
+
+            #[test]
+            fn scan_right_with_more_than_one() {
+                let instruction = Instruction::ScanRight(3);
+                assert_eq!(format!("{}", instruction), "[>>>]");
+            }
+
+            #[test]
+            fn multiply_add_with_one_target() {
+                let instruction = Instruction::MultiplyAdd(vec![(1, 1)]);
+                assert_eq!(format!("{}", instruction), "[->+<]");
+            }
+
+            #[test]
+            fn multiply_add_with_more_than_one_target() {
+                let instruction = Instruction::MultiplyAdd(vec![(1, 1), (2, 2), (-1, -3)]);
+                assert_eq!(format!("{}", instruction), "[->+>++<<<--->]");
+            }
+
+            #[test]
+            fn increment_at() {
+                let instruction = Instruction::IncrementAt(2, 3);
+                assert_eq!(format!("{}", instruction), ">>+++<<");
+
+                let instruction = Instruction::IncrementAt(-1, 1);
+                assert_eq!(format!("{}", instruction), "<+>");
+            }
+
+            #[test]
+            fn decrement_at() {
+                let instruction = Instruction::DecrementAt(2, 3);
+                assert_eq!(format!("{}", instruction), ">>---<<");
+
+                let instruction = Instruction::DecrementAt(-1, 1);
+                assert_eq!(format!("{}", instruction), "<->");
+            }
+
+            #[test]
+            fn read_at() {
+                let instruction = Instruction::ReadAt(3);
+                assert_eq!(format!("{}", instruction), ">>>,<<<");
+
+                let instruction = Instruction::ReadAt(-2);
+                assert_eq!(format!("{}", instruction), "<<,>>");
+            }
+
+            #[test]
+            fn write_at() {
+                let instruction = Instruction::WriteAt(3);
+                assert_eq!(format!("{}", instruction), ">>>.<<<");
+
+                let instruction = Instruction::WriteAt(-2);
+                assert_eq!(format!("{}", instruction), "<<.>>");
+            }
+
+            #[test]
+            fn dump() {
+                let instruction = Instruction::Dump;
+                assert_eq!(format!("{}", instruction), "#");
+            }
         }
     }
 }
@@ -1,9 +1,21 @@
 use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        Configuration,
+        EndOfInputPolicy,
+    },
     error::{
         RuntimeError,
         SyntaxError,
     },
     instruction::Instruction,
+    machine::Machine,
+    optimizer,
+    span::{
+        Location,
+        Span,
+    },
+    tape::Tape,
 };
 use std::{
     convert::TryFrom,
@@ -12,9 +24,11 @@ use std::{
         Display,
     },
     io::{
+        self,
         Read,
         Write,
     },
+    time::Instant,
 };
 
 pub const MEMORY_SIZE: usize = 30_000;
@@ -33,6 +47,9 @@ mod constants {
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Interpreter {
     program: Vec<Instruction>,
+    spans: Vec<Span>,
+    embedded_input: Option<Vec<u8>>,
+    configuration: Configuration,
 }
 
 impl Interpreter {
@@ -40,88 +57,252 @@ impl Interpreter {
     pub fn new() -> Interpreter {
         Interpreter::default()
     }
+
+    #[inline]
+    pub fn with_configuration(configuration: Configuration) -> Interpreter {
+        Interpreter {
+            program: Vec::new(),
+            spans: Vec::new(),
+            embedded_input: None,
+            configuration,
+        }
+    }
 }
 
 impl Interpreter {
+    #[inline]
+    pub fn configuration(&self) -> &Configuration {
+        &self.configuration
+    }
+
+    #[inline]
+    pub fn configuration_mut(&mut self) -> &mut Configuration {
+        &mut self.configuration
+    }
+
+    #[inline]
+    pub fn program(&self) -> &[Instruction] {
+        &self.program
+    }
+
+    #[inline]
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    #[inline]
+    pub fn span(&self, instruction: usize) -> Option<Span> {
+        self.spans.get(instruction).cloned()
+    }
+
+    #[inline]
+    pub(crate) fn embedded_input(&self) -> Option<&[u8]> {
+        self.embedded_input.as_deref()
+    }
+
+    #[inline]
+    pub(crate) fn set_program(
+        &mut self,
+        program: Vec<Instruction>,
+        spans: Vec<Span>,
+        embedded_input: Option<Vec<u8>>,
+    ) {
+        self.program = program;
+        self.spans = spans;
+        self.embedded_input = embedded_input;
+    }
+
+    // With the extensions enabled, everything after the first '!' is the input of
+    // the program instead of the actual input.
+    pub fn split_script<'s>(&self, script: &'s [u8]) -> (&'s [u8], Option<&'s [u8]>) {
+        if self.configuration.extensions {
+            if let Some(separator) = script.iter().position(|&token| token == b'!') {
+                return (&script[..separator], Some(&script[separator + 1..]));
+            }
+        }
+        (script, None)
+    }
+}
+
+impl Interpreter {
+    // Returns the final tape as bytes (i.e., a byte per cell with the default
+    // configuration), as it did before the tape became configurable. Use
+    // `execute_into_tape` to get the tape itself.
     pub fn execute(&self) -> Result<Vec<u8>, RuntimeError> {
         let stdin = std::io::stdin();
-        let mut stdin = stdin.lock().bytes();
+        let stdout = std::io::stdout();
+        self.execute_with(stdin.lock(), stdout.lock())
+    }
+
+    pub fn execute_with<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+    ) -> Result<Vec<u8>, RuntimeError> {
+        self.execute_into_tape_with(input, output)
+            .map(Tape::into_bytes)
+    }
 
+    pub fn execute_into_tape(&self) -> Result<Tape, RuntimeError> {
+        let stdin = std::io::stdin();
         let stdout = std::io::stdout();
-        let mut stdout = stdout.lock();
+        self.execute_into_tape_with(stdin.lock(), stdout.lock())
+    }
 
-        let mut tape = vec![0u8; MEMORY_SIZE];
-        let mut reading_head_location = 0usize;
+    pub fn execute_into_tape_with<R: Read, W: Write>(
+        &self,
+        input: R,
+        mut output: W,
+    ) -> Result<Tape, RuntimeError> {
+        self.with_input(input, |mut input| {
+            let mut machine = Machine::new(self);
+            while !machine.is_finished() {
+                machine.check_limits()?;
+                machine.step(&mut input, &mut output)?;
+            }
+            output.flush()?;
 
-        let mut current_instruction_index = 0usize;
-        while current_instruction_index != self.program.len() {
-            match &self.program[current_instruction_index] {
-                Instruction::MoveLeft(amount) => {
-                    if *amount > reading_head_location {
-                        return Err(RuntimeError::CellUnderflow);
-                    }
-                    reading_head_location -= *amount;
-                },
-                Instruction::MoveRight(amount) => {
-                    if *amount > MEMORY_SIZE - (reading_head_location + 1) {
-                        return Err(RuntimeError::CellOverflow);
-                    }
-                    reading_head_location += *amount;
-                },
-                Instruction::Increment(amount) => {
-                    tape[reading_head_location] = tape[reading_head_location].wrapping_add(*amount);
-                },
-                Instruction::Decrement(amount) => {
-                    tape[reading_head_location] = tape[reading_head_location].wrapping_sub(*amount);
-                },
-                Instruction::Read => {
-                    match stdin.next() {
-                        None => tape[reading_head_location] = 0,
-                        Some(maybe_read) => tape[reading_head_location] = maybe_read?,
-                    }
-                },
-                Instruction::Write => {
-                    stdout.write_all(&tape[reading_head_location..=reading_head_location])?;
-                },
-                Instruction::StartLoop(end_of_loop) => {
-                    if tape[reading_head_location] == 0 {
-                        current_instruction_index = *end_of_loop;
-                    }
-                },
-                Instruction::EndLoop(start_of_loop) => {
-                    if tape[reading_head_location] != 0 {
-                        current_instruction_index = *start_of_loop;
-                    }
-                },
+            Ok(machine.into_tape())
+        })
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.configuration
+            .timeout
+            .and_then(|timeout| Instant::now().checked_add(timeout))
+    }
+
+    // The input is read byte by byte without buffering, so the bytes after the ones
+    // the program consumes are left in the input for the caller (e.g., to share
+    // stdin with the debugger). As a result, unbuffered inputs (e.g., files)
+    // should be wrapped in a BufReader by the caller.
+    #[allow(clippy::unbuffered_bytes)]
+    pub(crate) fn with_input<R: Read, T>(
+        &self,
+        input: R,
+        f: impl FnOnce(&mut dyn Iterator<Item = io::Result<u8>>) -> T,
+    ) -> T {
+        match self.embedded_input() {
+            Some(embedded_input) => f(&mut embedded_input.bytes()),
+            None => f(&mut input.bytes()),
+        }
+    }
+
+    pub(crate) fn locate(
+        &self,
+        error: RuntimeError,
+        index: usize,
+        head: usize,
+        tape: &Tape,
+    ) -> RuntimeError {
+        let mut span = self.spans[index];
+        // A failed arithmetic instruction leaves its cell untouched, so the number of
+        // the commands of the run, which are applied before crossing the limit,
+        // follows from the cell.
+        let applied = match (&error, &self.program[index]) {
+            (RuntimeError::ValueOverflow, Instruction::Increment(_)) => {
+                tape.get(head).map(|cell| tape.cell_width().max() - cell)
+            },
+            (RuntimeError::ValueOverflow, Instruction::IncrementAt(offset, _)) => {
+                tape.get(head.wrapping_add(*offset as usize))
+                    .map(|cell| tape.cell_width().max() - cell)
+            },
+            (RuntimeError::ValueUnderflow, Instruction::Decrement(_)) => tape.get(head),
+            (RuntimeError::ValueUnderflow, Instruction::DecrementAt(offset, _)) => {
+                tape.get(head.wrapping_add(*offset as usize))
+            },
+            _ => None,
+        };
+        if let Some(applied) = applied {
+            let column = span.start.column + applied as usize;
+            if span.start.line == span.end.line && column <= span.end.column {
+                span = Span::at(Location::new(span.start.line, column));
             }
-            current_instruction_index += 1;
         }
 
-        Ok(tape)
+        RuntimeError::Located {
+            error: Box::new(error),
+            span,
+            instruction: self.program[index].clone(),
+            index,
+            head,
+        }
+    }
+
+    pub(crate) fn read<I: Iterator<Item = io::Result<u8>>>(
+        &self,
+        input: &mut I,
+        tape: &mut Tape,
+        target: usize,
+    ) -> Result<(), RuntimeError> {
+        match input.next() {
+            None => {
+                match self.configuration.end_of_input {
+                    EndOfInputPolicy::Zero => tape.set(target, 0),
+                    EndOfInputPolicy::Max => tape.set(target, tape.cell_width().max()),
+                    EndOfInputPolicy::Unchanged => {},
+                }
+            },
+            Some(maybe_read) => tape.set(target, u32::from(maybe_read?)),
+        }
+        Ok(())
     }
 
     pub fn load(&mut self, script: &[u8]) -> Result<(), SyntaxError> {
+        self.parse(script, false)
+            .map_err(|mut errors| errors.remove(0))
+    }
+
+    // Unlike load, which stops at the first unmatched bracket, keeps parsing until
+    // the end of the script and returns all unmatched brackets in the order
+    // they appear in the script.
+    pub fn load_collecting_errors(&mut self, script: &[u8]) -> Result<(), Vec<SyntaxError>> {
+        self.parse(script, true)
+    }
+
+    fn parse(&mut self, script: &[u8], collect_errors: bool) -> Result<(), Vec<SyntaxError>> {
+        let (script, embedded_input) = self.split_script(script);
+
+        let mut errors = Vec::new();
+
         let mut current_line = 1;
         let mut current_column = 1;
 
         let mut loop_balancer = Vec::new();
 
+        let cancel_opposite_arithmetic = self.configuration.arithmetic == ArithmeticPolicy::Wrap;
+        // A trapping run is only folded while its commands are adjacent, so the
+        // command, which crossed the limit, is the one at the number of the
+        // applied commands from its start.
+        let fold_across_gaps = self.configuration.arithmetic != ArithmeticPolicy::Trap;
+        let foldable = |span: &Span, location: Location| {
+            fold_across_gaps ||
+                (span.end.line == location.line && span.end.column + 1 == location.column)
+        };
+
         let mut new_program = Vec::with_capacity(script.len());
+        let mut new_spans: Vec<Span> = Vec::with_capacity(script.len());
         for token in script {
+            let location = Location::new(current_line, current_column);
             match token {
                 b'<' => {
                     match new_program.last_mut() {
                         Some(Instruction::MoveLeft(amount)) => {
                             *amount += 1;
+                            new_spans.last_mut().unwrap().end = location;
                         },
                         Some(Instruction::MoveRight(amount)) => {
                             *amount -= 1;
                             if *amount == 0 {
                                 new_program.pop();
+                                new_spans.pop();
+                            } else {
+                                new_spans.last_mut().unwrap().end = location;
                             }
                         },
                         _ => {
                             new_program.push(Instruction::MoveLeft(1));
+                            new_spans.push(Span::at(location));
                         },
                     };
                 },
@@ -129,86 +310,109 @@ impl Interpreter {
                     match new_program.last_mut() {
                         Some(Instruction::MoveRight(amount)) => {
                             *amount += 1;
+                            new_spans.last_mut().unwrap().end = location;
                         },
                         Some(Instruction::MoveLeft(amount)) => {
                             *amount -= 1;
                             if *amount == 0 {
                                 new_program.pop();
+                                new_spans.pop();
+                            } else {
+                                new_spans.last_mut().unwrap().end = location;
                             }
                         },
                         _ => {
                             new_program.push(Instruction::MoveRight(1));
+                            new_spans.push(Span::at(location));
                         },
                     };
                 },
                 b'+' => {
                     match new_program.last_mut() {
-                        Some(Instruction::Increment(amount)) => {
-                            *amount = amount.wrapping_add(1);
-                            if *amount == 0 {
-                                new_program.pop();
-                            }
+                        Some(Instruction::Increment(amount))
+                            if foldable(new_spans.last().unwrap(), location) =>
+                        {
+                            *amount += 1;
+                            new_spans.last_mut().unwrap().end = location;
                         },
-                        Some(Instruction::Decrement(amount)) => {
+                        Some(Instruction::Decrement(amount)) if cancel_opposite_arithmetic => {
                             *amount -= 1;
                             if *amount == 0 {
                                 new_program.pop();
+                                new_spans.pop();
+                            } else {
+                                new_spans.last_mut().unwrap().end = location;
                             }
                         },
                         _ => {
                             new_program.push(Instruction::Increment(1));
+                            new_spans.push(Span::at(location));
                         },
                     };
                 },
                 b'-' => {
                     match new_program.last_mut() {
-                        Some(Instruction::Decrement(amount)) => {
-                            *amount = amount.wrapping_add(1);
-                            if *amount == 0 {
-                                new_program.pop();
-                            }
+                        Some(Instruction::Decrement(amount))
+                            if foldable(new_spans.last().unwrap(), location) =>
+                        {
+                            *amount += 1;
+                            new_spans.last_mut().unwrap().end = location;
                         },
-                        Some(Instruction::Increment(amount)) => {
+                        Some(Instruction::Increment(amount)) if cancel_opposite_arithmetic => {
                             *amount -= 1;
                             if *amount == 0 {
                                 new_program.pop();
+                                new_spans.pop();
+                            } else {
+                                new_spans.last_mut().unwrap().end = location;
                             }
                         },
                         _ => {
                             new_program.push(Instruction::Decrement(1));
+                            new_spans.push(Span::at(location));
                         },
                     };
                 },
                 b',' => {
                     new_program.push(Instruction::Read);
+                    new_spans.push(Span::at(location));
                 },
                 b'.' => {
                     new_program.push(Instruction::Write);
+                    new_spans.push(Span::at(location));
                 },
                 b'[' => {
-                    loop_balancer.push((new_program.len(), current_line, current_column));
+                    loop_balancer.push((new_program.len(), location));
                     new_program.push(Instruction::StartLoop(0));
+                    new_spans.push(Span::at(location));
                 },
                 b']' => {
-                    let matching_start_loop_instruction_index =
-                        if let Some((index, _, _)) = loop_balancer.pop() {
-                            index
-                        } else {
-                            return Err(SyntaxError::MissingOpeningBracket(
-                                current_line,
-                                current_column,
-                            ));
-                        };
+                    if let Some((matching_start_loop_instruction_index, _)) = loop_balancer.pop() {
+                        let current_instruction_index = new_program.len();
+                        match &mut new_program[matching_start_loop_instruction_index] {
+                            Instruction::StartLoop(matching_end_loop_instruction_index) => {
+                                *matching_end_loop_instruction_index = current_instruction_index;
+                            },
+                            _ => unreachable!(),
+                        }
 
-                    let current_instruction_index = new_program.len();
-                    match &mut new_program[matching_start_loop_instruction_index] {
-                        Instruction::StartLoop(matching_end_loop_instruction_index) => {
-                            *matching_end_loop_instruction_index = current_instruction_index;
-                        },
-                        _ => unreachable!(),
-                    }
+                        new_program
+                            .push(Instruction::EndLoop(matching_start_loop_instruction_index));
 
-                    new_program.push(Instruction::EndLoop(matching_start_loop_instruction_index));
+                        new_spans.push(Span::at(location));
+                    } else {
+                        errors.push(SyntaxError::MissingOpeningBracket(
+                            current_line,
+                            current_column,
+                        ));
+                        if !collect_errors {
+                            return Err(errors);
+                        }
+                    }
+                },
+                b'#' if self.configuration.extensions => {
+                    new_program.push(Instruction::Dump);
+                    new_spans.push(Span::at(location));
                 },
                 b'\n' => {
                     current_line += 1;
@@ -219,13 +423,33 @@ impl Interpreter {
             current_column += 1;
         }
 
-        if !loop_balancer.is_empty() {
-            let (_, line, column) = loop_balancer.pop().unwrap();
-            return Err(SyntaxError::MissingClosingBracket(line, column));
+        if collect_errors {
+            errors.extend(loop_balancer.into_iter().map(|(_, location)| {
+                SyntaxError::MissingClosingBracket(location.line, location.column)
+            }));
+            errors.sort_by_key(SyntaxError::location);
+        } else if let Some((_, location)) = loop_balancer.pop() {
+            errors.push(SyntaxError::MissingClosingBracket(
+                location.line,
+                location.column,
+            ));
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        if self.configuration.optimize {
+            let (optimized_program, optimized_spans) =
+                optimizer::optimize(new_program, new_spans, &self.configuration);
+            new_program = optimized_program;
+            new_spans = optimized_spans;
         }
 
         new_program.shrink_to_fit();
+        new_spans.shrink_to_fit();
         self.program = new_program;
+        self.spans = new_spans;
+        self.embedded_input = embedded_input.map(<[u8]>::to_vec);
 
         Ok(())
     }
@@ -234,9 +458,7 @@ impl Interpreter {
 impl Default for Interpreter {
     #[inline]
     fn default() -> Interpreter {
-        Interpreter {
-            program: Vec::new(),
-        }
+        Interpreter::with_configuration(Configuration::default())
     }
 }
 
@@ -273,11 +495,735 @@ mod interpreter {
                 assert_eq!(interpreter, Interpreter::default());
             }
         }
+
+        mod with_configuration {
+            use crate::{
+                configuration::{
+                    ArithmeticPolicy,
+                    CellWidth,
+                    Configuration,
+                    EndOfInputPolicy,
+                    TapeGrowth,
+                },
+                interpreter::Interpreter,
+            };
+            use std::time::Duration;
+
+            #[test]
+            fn with_configuration() {
+                let configuration = Configuration {
+                    tape_size: 42,
+                    cell_width: CellWidth::U32,
+                    tape_growth: TapeGrowth::Both,
+                    arithmetic: ArithmeticPolicy::Trap,
+                    end_of_input: EndOfInputPolicy::Unchanged,
+                    optimize: true,
+                    extensions: true,
+                    max_steps: Some(1_000),
+                    timeout: Some(Duration::from_secs(1)),
+                };
+                let interpreter = Interpreter::with_configuration(configuration.clone());
+                assert_eq!(interpreter.configuration(), &configuration);
+            }
+        }
+    }
+
+    mod methods {
+        mod execute_with {
+            use crate::{
+                configuration::{
+                    CellWidth,
+                    Configuration,
+                },
+                interpreter::{
+                    Interpreter,
+                    MEMORY_SIZE,
+                },
+            };
+
+            #[test]
+            fn tape_bytes() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load(b"+>++").unwrap();
+                let tape = interpreter.execute_with(&b""[..], Vec::new()).unwrap();
+                assert_eq!(tape.len(), MEMORY_SIZE);
+                assert_eq!(&tape[..3], &[1, 2, 0]);
+
+                let configuration = Configuration {
+                    tape_size: 2,
+                    cell_width: CellWidth::U16,
+                    ..Configuration::default()
+                };
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(b"->++").unwrap();
+                let tape = interpreter.execute_with(&b""[..], Vec::new()).unwrap();
+                assert_eq!(tape, [0xff, 0xff, 2, 0]);
+            }
+
+            #[test]
+            fn unread_input() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load(b",.").unwrap();
+                let mut input = &b"abc"[..];
+                let mut output = Vec::new();
+                interpreter.execute_with(&mut input, &mut output).unwrap();
+                assert_eq!(output, b"a");
+                assert_eq!(input, b"bc");
+            }
+        }
+
+        mod execute_into_tape_with {
+            use crate::{
+                configuration::{
+                    ArithmeticPolicy,
+                    CellWidth,
+                    Configuration,
+                    EndOfInputPolicy,
+                    TapeGrowth,
+                },
+                error::RuntimeError,
+                instruction::Instruction,
+                interpreter::{
+                    Interpreter,
+                    MEMORY_SIZE,
+                },
+                span::{
+                    Location,
+                    Span,
+                },
+                tape::Tape,
+            };
+            use std::{
+                mem,
+                time::Duration,
+            };
+
+            fn run(script: &[u8], input: &[u8]) -> Result<(Vec<u8>, Tape), RuntimeError> {
+                run_with(Configuration::default(), script, input)
+            }
+
+            fn run_with(
+                configuration: Configuration,
+                script: &[u8],
+                input: &[u8],
+            ) -> Result<(Vec<u8>, Tape), RuntimeError> {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                let mut output = Vec::new();
+                let tape = interpreter.execute_into_tape_with(input, &mut output)?;
+                Ok((output, tape))
+            }
+
+            #[test]
+            fn hello_world() {
+                let (output, _) = run(include_bytes!("../assets/hello-world.bf"), b"").unwrap();
+                assert_eq!(output, b"Hello World!\n");
+            }
+
+            #[test]
+            fn cat() {
+                let (output, _) =
+                    run(include_bytes!("../assets/cat.bf"), b"Hello World!\n").unwrap();
+                assert_eq!(output, b"Hello World!\n");
+            }
+
+            #[test]
+            fn rot13() {
+                let (output, _) =
+                    run(include_bytes!("../assets/rot13.bf"), b"Hello World!\n").unwrap();
+                assert_eq!(output, b"Uryyb Jbeyq!\n");
+            }
+
+            #[test]
+            fn read_after_end_of_input() {
+                let (_, tape) = run(b"+,", b"").unwrap();
+                assert_eq!(tape[0], 0);
+            }
+
+            #[test]
+            fn read_after_end_of_input_with_max() {
+                let configuration = Configuration {
+                    end_of_input: EndOfInputPolicy::Max,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration.clone(), b"+,", b"").unwrap();
+                assert_eq!(tape[0], 255);
+
+                let configuration = Configuration {
+                    cell_width: CellWidth::U16,
+                    ..configuration
+                };
+                let (_, tape) = run_with(configuration, b",>,", b"a").unwrap();
+                assert_eq!(&tape.cells()[..2], &[97, 65_535]);
+            }
+
+            #[test]
+            fn read_after_end_of_input_with_unchanged() {
+                let configuration = Configuration {
+                    end_of_input: EndOfInputPolicy::Unchanged,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration, b"+++,>++,", b"a").unwrap();
+                assert_eq!(&tape.cells()[..2], &[97, 2]);
+            }
+
+            #[test]
+            fn cat_with_every_end_of_input_policy() {
+                let scripts: &[(EndOfInputPolicy, &[u8])] = &[
+                    (EndOfInputPolicy::Zero, b",[.,]"),
+                    (EndOfInputPolicy::Max, b",+[-.,+]"),
+                    (EndOfInputPolicy::Unchanged, b",[.[-],]"),
+                ];
+                for (end_of_input, script) in scripts {
+                    let configuration = Configuration {
+                        end_of_input: *end_of_input,
+                        ..Configuration::default()
+                    };
+                    let (output, _) = run_with(configuration, script, b"Hello World!\n").unwrap();
+                    assert_eq!(output, b"Hello World!\n");
+                }
+            }
+
+            #[test]
+            fn tape() {
+                let (output, tape) = run(b"+++>++>+<", b"").unwrap();
+                assert!(output.is_empty());
+                assert_eq!(tape.len(), MEMORY_SIZE);
+                assert_eq!(&tape.cells()[..4], &[3, 2, 1, 0]);
+            }
+
+            #[test]
+            fn tape_size() {
+                let configuration = Configuration {
+                    tape_size: 3,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration.clone(), b">>+", b"").unwrap();
+                assert_eq!(tape.cells(), &[0, 0, 1]);
+                match run_with(configuration, b">>>", b"")
+                    .unwrap_err()
+                    .without_location()
+                {
+                    RuntimeError::CellOverflow => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn cell_width() {
+                let script = b"-[>+<-]>.";
+                let (output, tape) = run(script, b"").unwrap();
+                assert_eq!(output, &[255]);
+                assert_eq!(tape[1], 255);
+
+                let configuration = Configuration {
+                    cell_width: CellWidth::U16,
+                    ..Configuration::default()
+                };
+                let (output, tape) = run_with(configuration, script, b"").unwrap();
+                assert_eq!(output, &[255]);
+                assert_eq!(tape[1], 65_535);
+            }
+
+            #[test]
+            fn increment_beyond_eight_bits() {
+                let script = "+".repeat(256);
+                let (_, tape) = run(script.as_bytes(), b"").unwrap();
+                assert_eq!(tape[0], 0);
+
+                let configuration = Configuration {
+                    cell_width: CellWidth::U32,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration, script.as_bytes(), b"").unwrap();
+                assert_eq!(tape[0], 256);
+            }
+
+            #[test]
+            fn saturating_arithmetic() {
+                let configuration = Configuration {
+                    arithmetic: ArithmeticPolicy::Saturate,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration.clone(), b"-+>+-", b"").unwrap();
+                assert_eq!(&tape.cells()[..2], &[1, 0]);
+
+                let script = "+".repeat(300);
+                let (_, tape) = run_with(configuration, script.as_bytes(), b"").unwrap();
+                assert_eq!(tape[0], 255);
+            }
+
+            #[test]
+            fn trapping_arithmetic() {
+                let configuration = Configuration {
+                    arithmetic: ArithmeticPolicy::Trap,
+                    ..Configuration::default()
+                };
+                let error = run_with(configuration.clone(), b">+<-+", b"").unwrap_err();
+                match error {
+                    RuntimeError::Located {
+                        ref error,
+                        index: 3,
+                        ..
+                    } => {
+                        match **error {
+                            RuntimeError::ValueUnderflow => {},
+                            _ => unreachable!(),
+                        }
+                    },
+                    _ => unreachable!(),
+                }
+
+                let script = "+".repeat(255) + ">+<+";
+                let error = run_with(configuration.clone(), script.as_bytes(), b"").unwrap_err();
+                match error {
+                    RuntimeError::Located {
+                        ref error,
+                        index: 4,
+                        ..
+                    } => {
+                        match **error {
+                            RuntimeError::ValueOverflow => {},
+                            _ => unreachable!(),
+                        }
+                    },
+                    _ => unreachable!(),
+                }
+
+                let (_, tape) = run_with(configuration, b"+++[-]+", b"").unwrap();
+                assert_eq!(tape[0], 1);
+            }
+
+            #[test]
+            fn trapping_arithmetic_locations() {
+                for &optimize in &[false, true] {
+                    let configuration = Configuration {
+                        arithmetic: ArithmeticPolicy::Trap,
+                        optimize,
+                        ..Configuration::default()
+                    };
+
+                    let script = "+".repeat(300);
+                    match run_with(configuration.clone(), script.as_bytes(), b"").unwrap_err() {
+                        RuntimeError::Located { span, .. } => {
+                            assert_eq!(span, Span::at(Location::new(1, 256)));
+                        },
+                        _ => unreachable!(),
+                    }
+
+                    let script = b">+++\n-- --<";
+                    match run_with(configuration, script, b"").unwrap_err() {
+                        RuntimeError::Located { span, .. } => {
+                            assert_eq!(span, Span::at(Location::new(2, 5)));
+                        },
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            #[test]
+            fn optimization_preserves_the_behavior() {
+                let scripts: &[(&[u8], &[u8])] = &[
+                    (include_bytes!("../assets/99-bottles-of-beer.bf"), b""),
+                    (include_bytes!("../assets/cat.bf"), b"Hello World!\n"),
+                    (include_bytes!("../assets/hello-world.bf"), b""),
+                    (include_bytes!("../assets/rot13.bf"), b"Hello World!\n"),
+                    (include_bytes!("../assets/sierpinski-triangle.bf"), b""),
+                    (b"+++++[->++>---<<]>>[+]<[<+>-]", b""),
+                    (b"++++[>+++>>>+<<<<-]>>>>[<]<<[>]>[->+>+<<]<<<<-[<+>-]", b""),
+                    (b">>+>>+[>>]+<<[<<]>>[->++<]", b""),
+                    (b">+++>++>+<<[>>.<<<.>-]>,<<.>>>[<<<+>>>-]<-.", b"a"),
+                ];
+                let configurations = &[
+                    Configuration::default(),
+                    Configuration {
+                        cell_width: CellWidth::U16,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        tape_size: 1,
+                        tape_growth: TapeGrowth::Both,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        arithmetic: ArithmeticPolicy::Trap,
+                        ..Configuration::default()
+                    },
+                ];
+                for configuration in configurations {
+                    for (script, input) in scripts {
+                        let optimized_configuration = Configuration {
+                            optimize: true,
+                            ..configuration.clone()
+                        };
+                        let expected = run_with(configuration.clone(), script, input);
+                        let actual = run_with(optimized_configuration, script, input);
+                        match (expected, actual) {
+                            (Ok(expected), Ok(actual)) => assert_eq!(expected, actual),
+                            (Err(expected), Err(actual)) => {
+                                assert_eq!(
+                                    mem::discriminant(expected.without_location()),
+                                    mem::discriminant(actual.without_location()),
+                                )
+                            },
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+            }
+
+            #[test]
+            fn optimization_preserves_the_errors() {
+                let scripts: &[&[u8]] = &[
+                    b"+[<]",
+                    b"+[>]",
+                    b"+[-<+>]",
+                    b"+[->+<]",
+                    b"+[-<+>>+<]",
+                    b"<+>",
+                    b">+<<",
+                    b">>.<<<",
+                    b"+[>+<<+>-]",
+                ];
+                for script in scripts {
+                    for tape_growth in &[TapeGrowth::Fixed, TapeGrowth::Right] {
+                        let configuration = Configuration {
+                            tape_size: 1,
+                            tape_growth: *tape_growth,
+                            ..Configuration::default()
+                        };
+                        let optimized_configuration = Configuration {
+                            optimize: true,
+                            ..configuration.clone()
+                        };
+                        let expected = run_with(configuration, script, b"");
+                        let actual = run_with(optimized_configuration, script, b"");
+                        match (expected, actual) {
+                            (Ok(expected), Ok(actual)) => assert_eq!(expected, actual),
+                            (Err(expected), Err(actual)) => {
+                                assert_eq!(
+                                    mem::discriminant(expected.without_location()),
+                                    mem::discriminant(actual.without_location()),
+                                )
+                            },
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+            }
+
+            #[test]
+            fn tape_growing_to_the_right() {
+                let configuration = Configuration {
+                    tape_size: 1,
+                    tape_growth: TapeGrowth::Right,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration.clone(), b">>>+", b"").unwrap();
+                assert_eq!(tape.cells(), &[0, 0, 0, 1]);
+                match run_with(configuration, b"<", b"")
+                    .unwrap_err()
+                    .without_location()
+                {
+                    RuntimeError::CellUnderflow => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn tape_growing_in_both_directions() {
+                let configuration = Configuration {
+                    tape_size: 1,
+                    tape_growth: TapeGrowth::Both,
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration, b"+<<++>>>+++", b"").unwrap();
+                assert_eq!(tape.cells(), &[2, 0, 1, 3]);
+            }
+
+            #[test]
+            fn cell_underflow() {
+                match run(b"+\n<", b"") {
+                    Err(RuntimeError::Located {
+                        error,
+                        span,
+                        instruction,
+                        index,
+                        head,
+                    }) => {
+                        match *error {
+                            RuntimeError::CellUnderflow => {},
+                            _ => unreachable!(),
+                        }
+                        assert_eq!(span, Span::at(Location::new(2, 1)));
+                        assert_eq!(instruction, Instruction::MoveLeft(1));
+                        assert_eq!(index, 1);
+                        assert_eq!(head, 0);
+                    },
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn cell_overflow() {
+                match run(b"+[>+]", b"").unwrap_err().without_location() {
+                    RuntimeError::CellOverflow => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn extensions() {
+                let script = b"+#>,.,.!ab";
+                let (output, tape) = run(script, b"cd").unwrap();
+                assert_eq!(output, b"cd");
+                assert_eq!(tape.get(1), Some(u32::from(b'd')));
+
+                let configuration = Configuration {
+                    extensions: true,
+                    ..Configuration::default()
+                };
+                let (output, tape) = run_with(configuration.clone(), script, b"cd").unwrap();
+                assert_eq!(output, b"ab");
+                assert_eq!(tape.get(0), Some(1));
+
+                let (output, _) = run_with(configuration, b",.!", b"cd").unwrap();
+                assert_eq!(output, b"\0");
+            }
+
+            #[test]
+            fn max_steps() {
+                let configuration = Configuration {
+                    max_steps: Some(3),
+                    ..Configuration::default()
+                };
+                let (_, tape) = run_with(configuration.clone(), b"+++", b"").unwrap();
+                assert_eq!(tape.get(0), Some(3));
+
+                match run_with(configuration.clone(), b">+>+", b"") {
+                    Err(RuntimeError::Located {
+                        error, index, head, ..
+                    }) => {
+                        match *error {
+                            RuntimeError::FuelExhausted(3) => {},
+                            _ => unreachable!(),
+                        }
+                        assert_eq!(index, 3);
+                        assert_eq!(head, 2);
+                    },
+                    _ => unreachable!(),
+                }
+
+                let configuration = Configuration {
+                    max_steps: Some(1_000),
+                    ..configuration
+                };
+                match run_with(configuration, b"+[>+<]", b"")
+                    .unwrap_err()
+                    .without_location()
+                {
+                    RuntimeError::FuelExhausted(1_000) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn timeout() {
+                let configuration = Configuration {
+                    timeout: Some(Duration::from_secs(0)),
+                    ..Configuration::default()
+                };
+                match run_with(configuration, b"+", b"")
+                    .unwrap_err()
+                    .without_location()
+                {
+                    RuntimeError::TimedOut(0) => {},
+                    _ => unreachable!(),
+                }
+
+                let configuration = Configuration {
+                    timeout: Some(Duration::from_millis(10)),
+                    ..Configuration::default()
+                };
+                match run_with(configuration, b"+[]", b"")
+                    .unwrap_err()
+                    .without_location()
+                {
+                    RuntimeError::TimedOut(steps) => assert!(*steps > 0),
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        mod load {
+            use crate::{
+                configuration::{
+                    ArithmeticPolicy,
+                    Configuration,
+                },
+                error::SyntaxError,
+                instruction::Instruction,
+                interpreter::Interpreter,
+                span::{
+                    Location,
+                    Span,
+                },
+            };
+
+            fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+                Span::new(Location::new(start.0, start.1), Location::new(end.0, end.1))
+            }
+
+            #[test]
+            fn spans() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load(b"++ +>\n <<-\n[.]").unwrap();
+                assert_eq!(
+                    interpreter.spans(),
+                    &[
+                        span((1, 1), (1, 4)),
+                        span((2, 3), (2, 3)),
+                        span((2, 4), (2, 4)),
+                        span((3, 1), (3, 1)),
+                        span((3, 2), (3, 2)),
+                        span((3, 3), (3, 3)),
+                    ],
+                );
+                assert_eq!(interpreter.span(1), Some(span((2, 3), (2, 3))));
+                assert_eq!(interpreter.span(6), None);
+
+                interpreter.load(b"+-><").unwrap();
+                assert!(interpreter.spans().is_empty());
+
+                interpreter.load(b"+++-").unwrap();
+                assert_eq!(interpreter.spans(), &[span((1, 1), (1, 4))]);
+            }
+
+            #[test]
+            fn trapping_spans() {
+                let mut interpreter = Interpreter::with_configuration(Configuration {
+                    arithmetic: ArithmeticPolicy::Trap,
+                    ..Configuration::default()
+                });
+                interpreter.load(b"++ +\n+--").unwrap();
+                assert_eq!(
+                    interpreter.program(),
+                    &[
+                        Instruction::Increment(2),
+                        Instruction::Increment(1),
+                        Instruction::Increment(1),
+                        Instruction::Decrement(2),
+                    ],
+                );
+                assert_eq!(
+                    interpreter.spans(),
+                    &[
+                        span((1, 1), (1, 2)),
+                        span((1, 4), (1, 4)),
+                        span((2, 1), (2, 1)),
+                        span((2, 2), (2, 3)),
+                    ],
+                );
+            }
+
+            #[test]
+            fn extensions() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load(b"+#!#").unwrap();
+                assert_eq!(interpreter.program(), &[Instruction::Increment(1)]);
+                assert_eq!(interpreter.embedded_input(), None);
+
+                let mut interpreter = Interpreter::with_configuration(Configuration {
+                    extensions: true,
+                    ..Configuration::default()
+                });
+                interpreter.load(b"+#\n#!#!").unwrap();
+                assert_eq!(
+                    interpreter.program(),
+                    &[
+                        Instruction::Increment(1),
+                        Instruction::Dump,
+                        Instruction::Dump
+                    ],
+                );
+                assert_eq!(
+                    interpreter.spans(),
+                    &[
+                        span((1, 1), (1, 1)),
+                        span((1, 2), (1, 2)),
+                        span((2, 1), (2, 1))
+                    ],
+                );
+                assert_eq!(interpreter.embedded_input(), Some(&b"#!"[..]));
+            }
+
+            #[test]
+            fn errors() {
+                let mut interpreter = Interpreter::new();
+                assert_eq!(
+                    interpreter.load(b"+]\n[[]\n]]").unwrap_err(),
+                    SyntaxError::MissingOpeningBracket(1, 2),
+                );
+                assert_eq!(
+                    interpreter.load(b"[\n+[[]").unwrap_err(),
+                    SyntaxError::MissingClosingBracket(2, 2),
+                );
+            }
+        }
+
+        mod load_collecting_errors {
+            use crate::{
+                error::SyntaxError,
+                instruction::Instruction,
+                interpreter::Interpreter,
+            };
+
+            #[test]
+            fn errors() {
+                let mut interpreter = Interpreter::new();
+                assert_eq!(
+                    interpreter
+                        .load_collecting_errors(b"[+]]\n[[-]\n]]>[")
+                        .unwrap_err(),
+                    vec![
+                        SyntaxError::MissingOpeningBracket(1, 4),
+                        SyntaxError::MissingOpeningBracket(3, 2),
+                        SyntaxError::MissingClosingBracket(3, 4),
+                    ],
+                );
+                assert_eq!(
+                    interpreter.load_collecting_errors(b"[\n[\n[]").unwrap_err(),
+                    vec![
+                        SyntaxError::MissingClosingBracket(1, 1),
+                        SyntaxError::MissingClosingBracket(2, 1),
+                    ],
+                );
+                assert!(interpreter.program().is_empty());
+            }
+
+            #[test]
+            fn valid() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load_collecting_errors(b"+[-]").unwrap();
+                assert_eq!(
+                    interpreter.program(),
+                    &[
+                        Instruction::Increment(1),
+                        Instruction::StartLoop(3),
+                        Instruction::Decrement(1),
+                        Instruction::EndLoop(1),
+                    ],
+                );
+            }
+        }
     }
 
     mod traits {
         mod default {
-            use crate::interpreter::Interpreter;
+            use crate::{
+                configuration::Configuration,
+                interpreter::Interpreter,
+            };
 
             #[test]
             fn default() {
@@ -285,7 +1231,10 @@ mod interpreter {
                 assert_eq!(
                     interpreter,
                     Interpreter {
-                        program: Vec::new()
+                        program: Vec::new(),
+                        spans: Vec::new(),
+                        embedded_input: None,
+                        configuration: Configuration::default(),
                     },
                 );
             }
@@ -0,0 +1,211 @@
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+use crate::{
+    error::RuntimeError,
+    interpreter::Interpreter,
+    tape::Tape,
+};
+use std::io::{
+    Read,
+    Write,
+};
+
+impl Interpreter {
+    pub fn execute_jit(&self) -> Result<Tape, RuntimeError> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        self.execute_jit_with(stdin.lock(), stdout.lock())
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    pub fn execute_jit_with<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+    ) -> Result<Tape, RuntimeError> {
+        // The machine code doesn't count the instructions it executes, so the limits
+        // are enforced by falling back to the interpreter.
+        let configuration = self.configuration();
+        if configuration.max_steps.is_some() || configuration.timeout.is_some() {
+            return self.execute_into_tape_with(input, output);
+        }
+        x86_64::execute(self, input, output)
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    pub fn execute_jit_with<R: Read, W: Write>(
+        &self,
+        input: R,
+        output: W,
+    ) -> Result<Tape, RuntimeError> {
+        self.execute_into_tape_with(input, output)
+    }
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod execute_jit_with {
+            use crate::{
+                configuration::{
+                    ArithmeticPolicy,
+                    CellWidth,
+                    Configuration,
+                    EndOfInputPolicy,
+                    TapeGrowth,
+                },
+                error::RuntimeError,
+                interpreter::Interpreter,
+                tape::Tape,
+            };
+            use std::{
+                fs,
+                time::Duration,
+            };
+
+            fn run(
+                configuration: &Configuration,
+                script: &[u8],
+                input: &[u8],
+                jit: bool,
+            ) -> Result<(Vec<u8>, Tape), (Vec<u8>, String)> {
+                let mut interpreter = Interpreter::with_configuration(configuration.clone());
+                interpreter.load(script).unwrap();
+                let mut output = Vec::new();
+                let result: Result<Tape, RuntimeError> = if jit {
+                    interpreter.execute_jit_with(input, &mut output)
+                } else {
+                    interpreter.execute_into_tape_with(input, &mut output)
+                };
+                match result {
+                    Ok(tape) => Ok((output, tape)),
+                    Err(error) => Err((output, format!("{:?}", error))),
+                }
+            }
+
+            fn assert_equivalent(configuration: &Configuration, script: &[u8], input: &[u8]) {
+                for &optimize in &[false, true] {
+                    let configuration = Configuration {
+                        optimize,
+                        ..configuration.clone()
+                    };
+                    assert_eq!(
+                        run(&configuration, script, input, true),
+                        run(&configuration, script, input, false),
+                    );
+                }
+            }
+
+            #[test]
+            fn assets() {
+                let mut assets = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/assets"))
+                    .unwrap()
+                    .map(|entry| entry.unwrap().path())
+                    .collect::<Vec<_>>();
+                assets.sort();
+                assert!(!assets.is_empty());
+
+                for asset in assets {
+                    let script = fs::read(asset).unwrap();
+                    assert_equivalent(&Configuration::default(), &script, b"Hello World!\n");
+                }
+            }
+
+            #[test]
+            fn cell_width() {
+                for &cell_width in &[CellWidth::U8, CellWidth::U16, CellWidth::U32] {
+                    let configuration = Configuration {
+                        cell_width,
+                        ..Configuration::default()
+                    };
+                    assert_equivalent(&configuration, b"-.>++[->+++<]>[->++<]>.", b"");
+                    assert_equivalent(&configuration, b"+++++[>++++++++<-]>>-.<<-.>+.", b"");
+                }
+            }
+
+            #[test]
+            fn arithmetic() {
+                for &arithmetic in &[
+                    ArithmeticPolicy::Wrap,
+                    ArithmeticPolicy::Saturate,
+                    ArithmeticPolicy::Trap,
+                ] {
+                    let configuration = Configuration {
+                        arithmetic,
+                        ..Configuration::default()
+                    };
+                    assert_equivalent(&configuration, b"+++.>>--.<<", b"");
+                    assert_equivalent(&configuration, b">++++++++[<++++++++>-]<[>++++<-]>.", b"");
+                }
+            }
+
+            #[test]
+            fn end_of_input() {
+                for &end_of_input in &[
+                    EndOfInputPolicy::Zero,
+                    EndOfInputPolicy::Max,
+                    EndOfInputPolicy::Unchanged,
+                ] {
+                    let configuration = Configuration {
+                        end_of_input,
+                        ..Configuration::default()
+                    };
+                    assert_equivalent(&configuration, b"+>,.>+++,.<<,.", b"a");
+                }
+            }
+
+            #[test]
+            fn tape_growth() {
+                let scripts: &[&[u8]] = &[
+                    b"+[<]",
+                    b"+[>]",
+                    b"+[-<+>]",
+                    b"+[->+<]",
+                    b"<+>",
+                    b">+<<",
+                    b">>.<<<",
+                    b"+[>+<<+>-]",
+                    b">+++>++>+<<[>>.<<<.>-]>,<<.>>>[<<<+>>>-]<-.",
+                    b"+>+>+[-]>>><<",
+                ];
+                for &tape_growth in &[TapeGrowth::Fixed, TapeGrowth::Right, TapeGrowth::Both] {
+                    let configuration = Configuration {
+                        tape_size: 1,
+                        tape_growth,
+                        ..Configuration::default()
+                    };
+                    for script in scripts {
+                        assert_equivalent(&configuration, script, b"a");
+                    }
+                }
+            }
+
+            #[test]
+            fn extensions() {
+                let configuration = Configuration {
+                    extensions: true,
+                    ..Configuration::default()
+                };
+                assert_equivalent(&configuration, b"+>#,.<[-#]>,.,.!ab", b"cd");
+                assert_equivalent(&configuration, b"+>#,.<[-#]>,.,.", b"cd");
+            }
+
+            #[test]
+            fn limits() {
+                let configuration = Configuration {
+                    max_steps: Some(1_000),
+                    ..Configuration::default()
+                };
+                assert_equivalent(&configuration, b"+[>+<]", b"");
+                assert_equivalent(&configuration, b"+++[->++<]>.", b"");
+
+                let configuration = Configuration {
+                    timeout: Some(Duration::from_secs(0)),
+                    ..Configuration::default()
+                };
+                assert_equivalent(&configuration, b"+[]", b"");
+            }
+        }
+    }
+}
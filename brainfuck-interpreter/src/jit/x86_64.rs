@@ -0,0 +1,448 @@
+use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        CellWidth,
+        Configuration,
+    },
+    error::RuntimeError,
+    instruction::Instruction,
+    interpreter::Interpreter,
+    tape::Tape,
+};
+use memmap::MmapMut;
+use std::{
+    convert::TryFrom,
+    io::{
+        self,
+        Read,
+        Write,
+    },
+    mem,
+    ptr,
+};
+
+// The machine code keeps the context in rbx, the head in r12, the address of
+// the first cell in r13 and the length of the tape in r14. Everything, which
+// may fail or grow the tape, is delegated to the functions below. They report
+// failures by returning FAILURE after storing the error in the context, and the
+// machine code reloads the registers from the context after calling them.
+// Before each call, the index of the current instruction and the head are
+// stored in the context to locate the failures. The rightmost cell reached by
+// the head is tracked in the context as well, since the machine code moves the
+// head without the tape.
+#[repr(C)]
+struct Context<'a> {
+    cells: *mut u32,
+    length: usize,
+    head: usize,
+    instruction: usize,
+    reached: usize,
+    tape: Tape,
+    interpreter: &'a Interpreter,
+    input: &'a mut dyn Iterator<Item = io::Result<u8>>,
+    output: &'a mut dyn Write,
+    error: Option<RuntimeError>,
+}
+
+const CELLS: u8 = 0;
+const LENGTH: u8 = 8;
+const HEAD: u8 = 16;
+const INSTRUCTION: u8 = 24;
+const REACHED: u8 = 32;
+
+const FAILURE: usize = usize::MAX;
+
+impl Context<'_> {
+    fn synchronize(&mut self) {
+        self.cells = self.tape.as_mut_ptr();
+        self.length = self.tape.len();
+    }
+
+    fn fail(&mut self, error: RuntimeError) -> usize {
+        self.error = Some(error);
+        FAILURE
+    }
+}
+
+extern "sysv64" fn locate(context: &mut Context, offset: isize) -> usize {
+    let mut head = context.head;
+    context.tape.reach(context.reached);
+    let located = context.tape.locate(&mut head, offset);
+    context.reached = context.tape.reached();
+    match located {
+        Ok(location) => {
+            context.head = head;
+            context.synchronize();
+            location
+        },
+        Err(error) => context.fail(error),
+    }
+}
+
+extern "sysv64" fn increment(context: &mut Context, index: usize, amount: usize) -> usize {
+    if context.tape.increment(index, amount) {
+        0
+    } else {
+        context.fail(RuntimeError::ValueOverflow)
+    }
+}
+
+extern "sysv64" fn decrement(context: &mut Context, index: usize, amount: usize) -> usize {
+    if context.tape.decrement(index, amount) {
+        0
+    } else {
+        context.fail(RuntimeError::ValueUnderflow)
+    }
+}
+
+extern "sysv64" fn read(context: &mut Context, index: usize) -> usize {
+    match context
+        .interpreter
+        .read(&mut context.input, &mut context.tape, index)
+    {
+        Ok(()) => 0,
+        Err(error) => context.fail(error),
+    }
+}
+
+extern "sysv64" fn write(context: &mut Context, index: usize) -> usize {
+    match context.output.write_all(&[context.tape[index] as u8]) {
+        Ok(()) => 0,
+        Err(error) => context.fail(RuntimeError::Io(error)),
+    }
+}
+
+extern "sysv64" fn dump(context: &mut Context, index: usize) -> usize {
+    let dumped = context
+        .output
+        .flush()
+        .and_then(|()| context.tape.dump(index, &mut io::stderr()));
+    match dumped {
+        Ok(()) => 0,
+        Err(error) => context.fail(RuntimeError::Io(error)),
+    }
+}
+
+pub(crate) fn execute<R: Read, W: Write>(
+    interpreter: &Interpreter,
+    input: R,
+    mut output: W,
+) -> Result<Tape, RuntimeError> {
+    let code = Assembler::new(interpreter.configuration()).assemble(interpreter.program());
+    let mut memory = MmapMut::map_anon(code.len())?;
+    memory.copy_from_slice(&code);
+    let memory = memory.make_exec()?;
+    let function =
+        unsafe { mem::transmute::<*const u8, extern "sysv64" fn(&mut Context)>(memory.as_ptr()) };
+
+    interpreter.with_input(input, |input| {
+        let mut context = Context {
+            cells: ptr::null_mut(),
+            length: 0,
+            head: 0,
+            instruction: 0,
+            reached: 0,
+            tape: Tape::new(interpreter.configuration()),
+            interpreter,
+            input,
+            output: &mut output,
+            error: None,
+        };
+        context.synchronize();
+        function(&mut context);
+
+        let Context {
+            mut tape,
+            head,
+            instruction,
+            reached,
+            error,
+            ..
+        } = context;
+        tape.reach(reached);
+        if let Some(error) = error {
+            return Err(interpreter.locate(error, instruction, head, &tape));
+        }
+        output.flush()?;
+
+        Ok(tape)
+    })
+}
+
+struct Assembler<'a> {
+    code: Vec<u8>,
+    configuration: &'a Configuration,
+    instruction: usize,
+    exits: Vec<usize>,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(configuration: &'a Configuration) -> Assembler<'a> {
+        Assembler {
+            code: Vec::new(),
+            configuration,
+            instruction: 0,
+            exits: Vec::new(),
+        }
+    }
+
+    fn assemble(mut self, program: &[Instruction]) -> Vec<u8> {
+        // push rbx; push r12; push r13; push r14; push r15 (keeps the stack aligned for
+        // calls)
+        self.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+        // mov rbx, rdi
+        self.emit(&[0x48, 0x89, 0xfb]);
+        self.reload();
+
+        let mut loops = Vec::new();
+        for (index, instruction) in program.iter().enumerate() {
+            self.instruction = index;
+            match *instruction {
+                Instruction::MoveLeft(amount) => {
+                    self.seek(-(amount as isize));
+                },
+                Instruction::MoveRight(amount) => {
+                    self.seek(amount as isize);
+                },
+                Instruction::Increment(amount) => {
+                    self.increment(0, amount);
+                },
+                Instruction::Decrement(amount) => {
+                    self.decrement(0, amount);
+                },
+                Instruction::Read => {
+                    self.io(0, read as *const ());
+                },
+                Instruction::Write => {
+                    self.io(0, write as *const ());
+                },
+                Instruction::StartLoop(_) => {
+                    self.load_current_cell();
+                    // je <end of loop>
+                    let end_of_loop = self.jump(&[0x0f, 0x84]);
+                    loops.push((end_of_loop, self.code.len()));
+                },
+                Instruction::EndLoop(_) => {
+                    let (end_of_loop, start_of_loop) = loops.pop().unwrap();
+                    self.load_current_cell();
+                    // jne <start of loop>
+                    let jump = self.jump(&[0x0f, 0x85]);
+                    self.patch(jump, start_of_loop);
+                    self.patch(end_of_loop, self.code.len());
+                },
+                Instruction::SetZero => {
+                    // mov dword [r13 + r12 * 4], 0
+                    self.emit(&[0x43, 0xc7, 0x44, 0xa5, 0x00, 0x00, 0x00, 0x00, 0x00]);
+                },
+                Instruction::ScanLeft(amount) => {
+                    self.scan(-(amount as isize));
+                },
+                Instruction::ScanRight(amount) => {
+                    self.scan(amount as isize);
+                },
+                Instruction::MultiplyAdd(ref targets) => {
+                    self.load_current_cell();
+                    // je <end of multiplication>
+                    let end_of_multiplication = self.jump(&[0x0f, 0x84]);
+                    // mov r15d, eax
+                    self.emit(&[0x41, 0x89, 0xc7]);
+                    for &(offset, factor) in targets {
+                        self.locate(offset);
+                        // imul edx, r15d, <factor>
+                        self.emit(&[0x41, 0x69, 0xd7]);
+                        self.emit(&(factor as i32).to_le_bytes());
+                        // add dword [r13 + rax * 4], edx
+                        self.emit(&[0x41, 0x01, 0x54, 0x85, 0x00]);
+                        self.mask();
+                    }
+                    // mov dword [r13 + r12 * 4], 0
+                    self.emit(&[0x43, 0xc7, 0x44, 0xa5, 0x00, 0x00, 0x00, 0x00, 0x00]);
+                    self.patch(end_of_multiplication, self.code.len());
+                },
+                Instruction::IncrementAt(offset, amount) => {
+                    self.increment(offset, amount);
+                },
+                Instruction::DecrementAt(offset, amount) => {
+                    self.decrement(offset, amount);
+                },
+                Instruction::ReadAt(offset) => {
+                    self.io(offset, read as *const ());
+                },
+                Instruction::WriteAt(offset) => {
+                    self.io(offset, write as *const ());
+                },
+                Instruction::Dump => {
+                    self.io(0, dump as *const ());
+                },
+            }
+        }
+
+        let epilogue = self.code.len();
+        for exit in mem::take(&mut self.exits) {
+            self.patch(exit, epilogue);
+        }
+        // pop r15; pop r14; pop r13; pop r12; pop rbx; ret
+        self.emit(&[0x41, 0x5f, 0x41, 0x5e, 0x41, 0x5d, 0x41, 0x5c, 0x5b, 0xc3]);
+
+        self.code
+    }
+}
+
+impl Assembler<'_> {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn jump(&mut self, opcode: &[u8]) -> usize {
+        self.emit(opcode);
+        self.emit(&[0x00; 4]);
+        self.code.len() - 4
+    }
+
+    fn patch(&mut self, jump: usize, target: usize) {
+        let displacement = target as isize - (jump + 4) as isize;
+        self.code[jump..jump + 4].copy_from_slice(&(displacement as i32).to_le_bytes());
+    }
+
+    fn reload(&mut self) {
+        // mov r12, [rbx + HEAD]; mov r13, [rbx + CELLS]; mov r14, [rbx + LENGTH]
+        self.emit(&[
+            0x4c, 0x8b, 0x63, HEAD, 0x4c, 0x8b, 0x6b, CELLS, 0x4c, 0x8b, 0x73, LENGTH,
+        ]);
+    }
+
+    fn call(&mut self, function: *const ()) {
+        // mov rax, <instruction>; mov [rbx + INSTRUCTION], rax; mov [rbx + HEAD], r12
+        self.emit(&[0x48, 0xb8]);
+        self.emit(&(self.instruction as u64).to_le_bytes());
+        self.emit(&[0x48, 0x89, 0x43, INSTRUCTION, 0x4c, 0x89, 0x63, HEAD]);
+        // mov rax, <function>; call rax
+        self.emit(&[0x48, 0xb8]);
+        self.emit(&(function as usize).to_le_bytes());
+        self.emit(&[0xff, 0xd0]);
+        // cmp rax, FAILURE; je <epilogue>
+        self.emit(&[0x48, 0x83, 0xf8, 0xff]);
+        let exit = self.jump(&[0x0f, 0x84]);
+        self.exits.push(exit);
+    }
+
+    fn load_current_cell(&mut self) {
+        // mov eax, dword [r13 + r12 * 4]; test eax, eax
+        self.emit(&[0x43, 0x8b, 0x44, 0xa5, 0x00, 0x85, 0xc0]);
+    }
+
+    // Leaves the index of the cell at the offset from the head in rax.
+    fn locate(&mut self, offset: isize) {
+        if offset == 0 {
+            // mov rax, r12
+            self.emit(&[0x4c, 0x89, 0xe0]);
+            return;
+        }
+
+        let displacement = i32::try_from(offset).ok();
+        let fast_path = displacement.map(|displacement| {
+            if displacement > 0 {
+                // lea rax, [r12 + <displacement>]; cmp rax, r14; jb <fast path>
+                self.emit(&[0x49, 0x8d, 0x84, 0x24]);
+                self.emit(&displacement.to_le_bytes());
+                self.emit(&[0x4c, 0x39, 0xf0]);
+                self.jump(&[0x0f, 0x82])
+            } else {
+                // cmp r12, <distance>; jae <fast path>
+                self.emit(&[0x49, 0x81, 0xfc]);
+                self.emit(&(-displacement).to_le_bytes());
+                self.jump(&[0x0f, 0x83])
+            }
+        });
+
+        // mov rdi, rbx; mov rsi, <offset>
+        self.emit(&[0x48, 0x89, 0xdf, 0x48, 0xbe]);
+        self.emit(&(offset as i64).to_le_bytes());
+        self.call(locate as *const ());
+        self.reload();
+
+        if let (Some(displacement), Some(fast_path)) = (displacement, fast_path) {
+            // jmp <end>
+            let end = self.jump(&[0xe9]);
+            self.patch(fast_path, self.code.len());
+            // lea rax, [r12 + <displacement>]
+            self.emit(&[0x49, 0x8d, 0x84, 0x24]);
+            self.emit(&displacement.to_le_bytes());
+            self.patch(end, self.code.len());
+        }
+
+        if offset > 0 {
+            // cmp rax, [rbx + REACHED]; jbe <end>; mov [rbx + REACHED], rax
+            self.emit(&[
+                0x48, 0x3b, 0x43, REACHED, 0x76, 0x04, 0x48, 0x89, 0x43, REACHED,
+            ]);
+        }
+    }
+
+    fn seek(&mut self, offset: isize) {
+        self.locate(offset);
+        // mov r12, rax
+        self.emit(&[0x49, 0x89, 0xc4]);
+    }
+
+    fn scan(&mut self, offset: isize) {
+        let start_of_scan = self.code.len();
+        self.load_current_cell();
+        // je <end of scan>
+        let end_of_scan = self.jump(&[0x0f, 0x84]);
+        self.seek(offset);
+        // jmp <start of scan>
+        let jump = self.jump(&[0xe9]);
+        self.patch(jump, start_of_scan);
+        self.patch(end_of_scan, self.code.len());
+    }
+
+    fn mask(&mut self) {
+        let mask: u32 = match self.configuration.cell_width {
+            CellWidth::U8 => 0xff,
+            CellWidth::U16 => 0xffff,
+            CellWidth::U32 => return,
+        };
+        // and dword [r13 + rax * 4], <mask>
+        self.emit(&[0x41, 0x81, 0x64, 0x85, 0x00]);
+        self.emit(&mask.to_le_bytes());
+    }
+
+    fn increment(&mut self, offset: isize, amount: usize) {
+        self.locate(offset);
+        if self.configuration.arithmetic == ArithmeticPolicy::Wrap {
+            // add dword [r13 + rax * 4], <amount>
+            self.emit(&[0x41, 0x81, 0x44, 0x85, 0x00]);
+            self.emit(&(amount as u32).to_le_bytes());
+            self.mask();
+        } else {
+            self.arithmetic(amount, increment as *const ());
+        }
+    }
+
+    fn decrement(&mut self, offset: isize, amount: usize) {
+        self.locate(offset);
+        if self.configuration.arithmetic == ArithmeticPolicy::Wrap {
+            // sub dword [r13 + rax * 4], <amount>
+            self.emit(&[0x41, 0x81, 0x6c, 0x85, 0x00]);
+            self.emit(&(amount as u32).to_le_bytes());
+            self.mask();
+        } else {
+            self.arithmetic(amount, decrement as *const ());
+        }
+    }
+
+    fn arithmetic(&mut self, amount: usize, function: *const ()) {
+        // mov rdi, rbx; mov rsi, rax; mov rdx, <amount>
+        self.emit(&[0x48, 0x89, 0xdf, 0x48, 0x89, 0xc6, 0x48, 0xba]);
+        self.emit(&(amount as u64).to_le_bytes());
+        self.call(function);
+    }
+
+    fn io(&mut self, offset: isize, function: *const ()) {
+        self.locate(offset);
+        // mov rdi, rbx; mov rsi, rax
+        self.emit(&[0x48, 0x89, 0xdf, 0x48, 0x89, 0xc6]);
+        self.call(function);
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{
+    error::{
+        RuntimeError,
+        SyntaxError,
+    },
+    span::Location,
+    tape::Tape,
+};
+use std::fmt::Write;
+
+fn string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if character.is_control() => {
+                write!(escaped, "\\u{:04x}", u32::from(character)).unwrap();
+            },
+            character => escaped.push(character),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn number(value: Option<usize>) -> String {
+    value.map_or_else(|| "null".to_owned(), |value| value.to_string())
+}
+
+fn object(
+    kind: &str,
+    message: &str,
+    path: &str,
+    location: Option<Location>,
+    index: Option<usize>,
+    head: Option<usize>,
+) -> String {
+    format!(
+        "{{\"kind\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"instruction_index\":\
+         {},\"head\":{}}}",
+        string(kind),
+        string(message),
+        string(path),
+        number(location.map(|location| location.line)),
+        number(location.map(|location| location.column)),
+        number(index),
+        number(head),
+    )
+}
+
+pub fn io_error_to_json(message: &str, path: &str) -> String {
+    object("io", message, path, None, None, None)
+}
+
+impl SyntaxError {
+    pub fn to_json(&self, path: &str) -> String {
+        let kind = match *self {
+            SyntaxError::MissingOpeningBracket(_, _) => "missing_opening_bracket",
+            SyntaxError::MissingClosingBracket(_, _) => "missing_closing_bracket",
+        };
+        object(
+            kind,
+            self.message(),
+            path,
+            Some(self.location()),
+            None,
+            None,
+        )
+    }
+}
+
+impl RuntimeError {
+    pub fn to_json(&self, path: &str) -> String {
+        let (location, index, head) = match *self {
+            RuntimeError::Located {
+                span, index, head, ..
+            } => (Some(span.start), Some(index), Some(head)),
+            _ => (None, None, None),
+        };
+
+        let error = self.without_location();
+        let kind = match error {
+            RuntimeError::Io(_) => "io",
+            RuntimeError::CellUnderflow => "cell_underflow",
+            RuntimeError::CellOverflow => "cell_overflow",
+            RuntimeError::ValueUnderflow => "value_underflow",
+            RuntimeError::ValueOverflow => "value_overflow",
+            RuntimeError::FuelExhausted(_) => "fuel_exhausted",
+            RuntimeError::TimedOut(_) => "timed_out",
+            RuntimeError::Located { .. } => unreachable!(),
+        };
+        object(kind, &error.message(), path, location, index, head)
+    }
+}
+
+impl Tape {
+    pub fn to_json(&self) -> String {
+        let cells = self
+            .used_cells()
+            .iter()
+            .map(u32::to_string)
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"cell_width\":{},\"cells\":[{}]}}",
+            self.cell_width().bits(),
+            cells.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod functions {
+    mod string {
+        use crate::json::string;
+
+        #[test]
+        fn escaping() {
+            assert_eq!(string("a.bf"), r#""a.bf""#);
+            assert_eq!(string("of '['"), r#""of '['""#);
+            assert_eq!(string("\"a\\b\"\n\t\r"), r#""\"a\\b\"\n\t\r""#);
+            assert_eq!(string("\u{1}ş"), r#""\u0001ş""#);
+        }
+    }
+
+    mod io_error_to_json {
+        use crate::json::io_error_to_json;
+
+        #[test]
+        fn unlocated() {
+            assert_eq!(
+                io_error_to_json("unable to open the INPUT file (not found)", "a.bf"),
+                concat!(
+                    r#"{"kind":"io","message":"unable to open the INPUT file (not found)","#,
+                    r#""file":"a.bf","line":null,"column":null,"#,
+                    r#""instruction_index":null,"head":null}"#,
+                ),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod syntax_error {
+    mod methods {
+        mod to_json {
+            use crate::error::SyntaxError;
+
+            #[test]
+            fn missing_opening_bracket() {
+                assert_eq!(
+                    SyntaxError::MissingOpeningBracket(2, 7).to_json("a.bf"),
+                    concat!(
+                        r#"{"kind":"missing_opening_bracket","#,
+                        r#""message":"unable to find the opening bracket of ']'","#,
+                        r#""file":"a.bf","line":2,"column":7,"instruction_index":null,"#,
+                        r#""head":null}"#,
+                    ),
+                );
+            }
+
+            #[test]
+            fn missing_closing_bracket() {
+                assert_eq!(
+                    SyntaxError::MissingClosingBracket(1, 3).to_json("b.bf"),
+                    concat!(
+                        r#"{"kind":"missing_closing_bracket","#,
+                        r#""message":"unable to find the closing bracket of '['","#,
+                        r#""file":"b.bf","line":1,"column":3,"instruction_index":null,"#,
+                        r#""head":null}"#,
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod runtime_error {
+    mod methods {
+        mod to_json {
+            use crate::{
+                error::RuntimeError,
+                instruction::Instruction,
+                span::{
+                    Location,
+                    Span,
+                },
+            };
+            use std::io;
+
+            #[test]
+            fn located() {
+                let error = RuntimeError::Located {
+                    error: Box::new(RuntimeError::CellUnderflow),
+                    span: Span::new(Location::new(3, 2), Location::new(3, 4)),
+                    instruction: Instruction::MoveLeft(3),
+                    index: 5,
+                    head: 1,
+                };
+                assert_eq!(
+                    error.to_json("a.bf"),
+                    concat!(
+                        r#"{"kind":"cell_underflow","#,
+                        r#""message":"attempted to access a negative cell","#,
+                        r#""file":"a.bf","line":3,"column":2,"instruction_index":5,"head":1}"#,
+                    ),
+                );
+            }
+
+            #[test]
+            fn unlocated() {
+                assert_eq!(
+                    RuntimeError::ValueOverflow.to_json("a.bf"),
+                    concat!(
+                        r#"{"kind":"value_overflow","#,
+                        r#""message":"attempted to increment a cell above its maximum value","#,
+                        r#""file":"a.bf","line":null,"column":null,"#,
+                        r#""instruction_index":null,"head":null}"#,
+                    ),
+                );
+
+                let error = RuntimeError::Io(io::ErrorKind::Other.into());
+                assert_eq!(
+                    error.to_json("a.bf"),
+                    format!(
+                        concat!(
+                            r#"{{"kind":"io","message":"unexpected io error occurred ({})","#,
+                            r#""file":"a.bf","line":null,"column":null,"#,
+                            r#""instruction_index":null,"head":null}}"#,
+                        ),
+                        io::Error::from(io::ErrorKind::Other),
+                    ),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tape {
+    mod methods {
+        mod to_json {
+            use crate::{
+                configuration::{
+                    CellWidth,
+                    Configuration,
+                },
+                tape::Tape,
+            };
+
+            #[test]
+            fn to_json() {
+                let mut tape = Tape::new(&Configuration::default());
+                assert_eq!(tape.to_json(), r#"{"cell_width":8,"cells":[0]}"#);
+                tape.set(0, 72);
+                tape.set(2, 105);
+                tape.move_right(0, 3).unwrap();
+                assert_eq!(tape.to_json(), r#"{"cell_width":8,"cells":[72,0,105,0]}"#);
+
+                let mut tape = Tape::new(&Configuration {
+                    cell_width: CellWidth::U32,
+                    ..Configuration::default()
+                });
+                tape.set(1, 70_000);
+                tape.move_right(0, 1).unwrap();
+                assert_eq!(tape.to_json(), r#"{"cell_width":32,"cells":[0,70000]}"#);
+            }
+        }
+    }
+}
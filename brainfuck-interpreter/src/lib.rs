@@ -1,6 +1,35 @@
+#![allow(clippy::module_inception)]
+
+mod bytecode;
+mod codegen;
+mod configuration;
+mod debugger;
+mod diagnostic;
+mod formatter;
 mod instruction;
 mod interpreter;
+#[cfg(feature = "jit")]
+mod jit;
+mod json;
+mod machine;
+mod optimizer;
+mod profiler;
+mod repl;
+mod snapshot;
+mod span;
+mod tape;
+mod trace;
 
 pub mod error;
 
+pub use codegen::*;
+pub use configuration::*;
+pub use formatter::*;
+pub use instruction::*;
 pub use interpreter::*;
+pub use json::io_error_to_json;
+pub use machine::*;
+pub use profiler::*;
+pub use span::*;
+pub use tape::*;
+pub use trace::*;
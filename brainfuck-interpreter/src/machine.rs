@@ -0,0 +1,421 @@
+use crate::{
+    error::RuntimeError,
+    instruction::Instruction,
+    interpreter::Interpreter,
+    tape::Tape,
+};
+use std::{
+    collections::VecDeque,
+    io::{
+        self,
+        Write,
+    },
+    iter,
+    mem,
+    time::Instant,
+};
+
+// Reading the clock takes much longer than executing an instruction, so the
+// deadline is only checked once in every DEADLINE_INTERVAL steps.
+const DEADLINE_INTERVAL: u64 = 4096;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Status {
+    Finished,
+    Paused,
+    NeedsInput,
+}
+
+pub struct Machine<'a> {
+    pub(crate) interpreter: &'a Interpreter,
+    pub(crate) tape: Tape,
+    pub(crate) head: usize,
+    pub(crate) instruction: usize,
+    pub(crate) steps: u64,
+    pub(crate) input: VecDeque<u8>,
+    pub(crate) end_of_input: bool,
+    pub(crate) output: Vec<u8>,
+    // The timeout counts from the creation of the machine, since it may be run in many slices.
+    pub(crate) deadline: Option<Instant>,
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(interpreter: &'a Interpreter) -> Machine<'a> {
+        Machine::with_tape(interpreter, Tape::new(interpreter.configuration()), 0)
+    }
+
+    // Starts the program on an existing tape, which is used to carry the tape
+    // between the entries of the REPL.
+    pub(crate) fn with_tape(interpreter: &'a Interpreter, tape: Tape, head: usize) -> Machine<'a> {
+        let embedded_input = interpreter.embedded_input();
+        Machine {
+            interpreter,
+            tape,
+            head,
+            instruction: 0,
+            steps: 0,
+            input: embedded_input
+                .map(|input| input.iter().copied().collect())
+                .unwrap_or_default(),
+            end_of_input: embedded_input.is_some(),
+            output: Vec::new(),
+            deadline: interpreter.deadline(),
+        }
+    }
+}
+
+impl Machine<'_> {
+    #[inline]
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    #[inline]
+    pub fn head(&self) -> usize {
+        self.head
+    }
+
+    #[inline]
+    pub fn instruction(&self) -> usize {
+        self.instruction
+    }
+
+    #[inline]
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.instruction == self.interpreter.program().len()
+    }
+
+    #[inline]
+    pub fn into_tape(self) -> Tape {
+        self.tape
+    }
+}
+
+impl Machine<'_> {
+    pub fn feed_input(&mut self, input: &[u8]) {
+        self.input.extend(input);
+    }
+
+    // Once the end of the input is reached, ',' follows the end of input policy
+    // instead of pausing the machine to wait for more input.
+    pub fn end_input(&mut self) {
+        self.end_of_input = true;
+    }
+
+    pub fn take_output(&mut self) -> Vec<u8> {
+        mem::take(&mut self.output)
+    }
+
+    // Executes at most the given number of instructions, and pauses before reading
+    // from the input if there is no input left and the end of the input is not
+    // reached yet.
+    pub fn run(&mut self, steps: u64) -> Result<Status, RuntimeError> {
+        let mut input = mem::take(&mut self.input);
+        let mut output = mem::take(&mut self.output);
+        let status = self.run_buffered(steps, &mut input, &mut output);
+        self.input = input;
+        self.output = output;
+        status
+    }
+
+    fn run_buffered(
+        &mut self,
+        steps: u64,
+        input: &mut VecDeque<u8>,
+        output: &mut Vec<u8>,
+    ) -> Result<Status, RuntimeError> {
+        for _ in 0..steps {
+            if self.is_finished() {
+                return Ok(Status::Finished);
+            }
+
+            let instruction = &self.interpreter.program()[self.instruction];
+            let reads = matches!(instruction, Instruction::Read | Instruction::ReadAt(_));
+            if reads && input.is_empty() && !self.end_of_input {
+                return Ok(Status::NeedsInput);
+            }
+
+            self.check_limits()?;
+            self.step(&mut iter::from_fn(|| input.pop_front().map(Ok)), output)?;
+        }
+        Ok(if self.is_finished() {
+            Status::Finished
+        } else {
+            Status::Paused
+        })
+    }
+}
+
+impl Machine<'_> {
+    pub(crate) fn step<I: Iterator<Item = io::Result<u8>>, W: Write>(
+        &mut self,
+        input: &mut I,
+        output: &mut W,
+    ) -> Result<(), RuntimeError> {
+        let interpreter = self.interpreter;
+        let instruction = self.instruction;
+        self.execute(input, output)
+            .map_err(|error| interpreter.locate(error, instruction, self.head, &self.tape))?;
+        self.steps += 1;
+        Ok(())
+    }
+
+    // Steps with the buffered input of the machine instead of an external one, but
+    // writes the output directly to the given output.
+    pub(crate) fn step_buffered<W: Write>(&mut self, output: &mut W) -> Result<(), RuntimeError> {
+        let mut input = mem::take(&mut self.input);
+        let result = self.step(&mut iter::from_fn(|| input.pop_front().map(Ok)), output);
+        self.input = input;
+        result
+    }
+
+    pub(crate) fn check_limits(&self) -> Result<(), RuntimeError> {
+        let configuration = self.interpreter.configuration();
+        let error = if configuration
+            .max_steps
+            .is_some_and(|max_steps| self.steps >= max_steps)
+        {
+            RuntimeError::FuelExhausted(self.steps)
+        } else if self.steps % DEADLINE_INTERVAL == 0 &&
+            self.deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            RuntimeError::TimedOut(self.steps)
+        } else {
+            return Ok(());
+        };
+        Err(self
+            .interpreter
+            .locate(error, self.instruction, self.head, &self.tape))
+    }
+
+    fn execute<I: Iterator<Item = io::Result<u8>>, W: Write>(
+        &mut self,
+        input: &mut I,
+        output: &mut W,
+    ) -> Result<(), RuntimeError> {
+        let tape = &mut self.tape;
+        let head = &mut self.head;
+        let current_instruction_index = self.instruction;
+        match &self.interpreter.program()[current_instruction_index] {
+            Instruction::MoveLeft(amount) => {
+                *head = tape.move_left(*head, *amount)?;
+            },
+            Instruction::MoveRight(amount) => {
+                *head = tape.move_right(*head, *amount)?;
+            },
+            Instruction::Increment(amount) => {
+                if !tape.increment(*head, *amount) {
+                    return Err(RuntimeError::ValueOverflow);
+                }
+            },
+            Instruction::Decrement(amount) => {
+                if !tape.decrement(*head, *amount) {
+                    return Err(RuntimeError::ValueUnderflow);
+                }
+            },
+            Instruction::Read => {
+                self.interpreter.read(input, tape, *head)?;
+            },
+            Instruction::Write => {
+                output.write_all(&[tape[*head] as u8])?;
+            },
+            Instruction::StartLoop(end_of_loop) => {
+                if tape[*head] == 0 {
+                    self.instruction = *end_of_loop;
+                }
+            },
+            Instruction::EndLoop(start_of_loop) => {
+                if tape[*head] != 0 {
+                    self.instruction = *start_of_loop;
+                }
+            },
+            Instruction::SetZero => {
+                tape.set(*head, 0);
+            },
+            Instruction::ScanLeft(amount) => {
+                while tape[*head] != 0 {
+                    *head = tape.move_left(*head, *amount)?;
+                }
+            },
+            Instruction::ScanRight(amount) => {
+                while tape[*head] != 0 {
+                    *head = tape.move_right(*head, *amount)?;
+                }
+            },
+            Instruction::MultiplyAdd(targets) => {
+                let value = tape[*head];
+                if value != 0 {
+                    for &(offset, factor) in targets {
+                        let target = tape.locate(head, offset)?;
+                        tape.multiply_add(target, value, factor);
+                    }
+                    tape.set(*head, 0);
+                }
+            },
+            Instruction::IncrementAt(offset, amount) => {
+                let target = tape.locate(head, *offset)?;
+                if !tape.increment(target, *amount) {
+                    return Err(RuntimeError::ValueOverflow);
+                }
+            },
+            Instruction::DecrementAt(offset, amount) => {
+                let target = tape.locate(head, *offset)?;
+                if !tape.decrement(target, *amount) {
+                    return Err(RuntimeError::ValueUnderflow);
+                }
+            },
+            Instruction::ReadAt(offset) => {
+                let target = tape.locate(head, *offset)?;
+                self.interpreter.read(input, tape, target)?;
+            },
+            Instruction::WriteAt(offset) => {
+                let target = tape.locate(head, *offset)?;
+                output.write_all(&[tape[target] as u8])?;
+            },
+            Instruction::Dump => {
+                output.flush()?;
+                tape.dump(*head, &mut io::stderr())?;
+            },
+        }
+        self.instruction += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod machine {
+    mod methods {
+        mod run {
+            use crate::{
+                configuration::Configuration,
+                error::RuntimeError,
+                interpreter::Interpreter,
+                machine::{
+                    Machine,
+                    Status,
+                },
+            };
+            use std::time::Duration;
+
+            fn load(configuration: Configuration, script: &[u8]) -> Interpreter {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                interpreter
+            }
+
+            #[test]
+            fn finished() {
+                let interpreter = load(
+                    Configuration::default(),
+                    include_bytes!("../assets/hello-world.bf"),
+                );
+                let mut machine = Machine::new(&interpreter);
+                assert_eq!(machine.run(u64::MAX).unwrap(), Status::Finished);
+                assert_eq!(machine.take_output(), b"Hello World!\n");
+                assert!(machine.take_output().is_empty());
+                assert_eq!(machine.run(1).unwrap(), Status::Finished);
+            }
+
+            #[test]
+            fn paused() {
+                let interpreter = load(Configuration::default(), b"+>++>+++");
+                let mut machine = Machine::new(&interpreter);
+                assert_eq!(machine.run(2).unwrap(), Status::Paused);
+                assert_eq!(machine.steps(), 2);
+                assert_eq!(machine.instruction(), 2);
+                assert_eq!(machine.head(), 1);
+
+                assert_eq!(machine.run(0).unwrap(), Status::Paused);
+                assert_eq!(machine.steps(), 2);
+
+                assert_eq!(machine.run(3).unwrap(), Status::Finished);
+                assert_eq!(machine.steps(), 5);
+                assert_eq!(machine.into_tape().cells()[..3], [1, 2, 3]);
+            }
+
+            #[test]
+            fn needs_input() {
+                let interpreter = load(Configuration::default(), b",.>,.");
+                let mut machine = Machine::new(&interpreter);
+                assert_eq!(machine.run(u64::MAX).unwrap(), Status::NeedsInput);
+                assert_eq!(machine.instruction(), 0);
+
+                machine.feed_input(b"a");
+                assert_eq!(machine.run(u64::MAX).unwrap(), Status::NeedsInput);
+                assert_eq!(machine.instruction(), 3);
+                assert_eq!(machine.take_output(), b"a");
+
+                machine.end_input();
+                assert_eq!(machine.run(u64::MAX).unwrap(), Status::Finished);
+                assert_eq!(machine.take_output(), b"\0");
+            }
+
+            #[test]
+            fn embedded_input() {
+                let configuration = Configuration {
+                    extensions: true,
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration, b",.,.,.!ab");
+                let mut machine = Machine::new(&interpreter);
+                assert_eq!(machine.run(u64::MAX).unwrap(), Status::Finished);
+                assert_eq!(machine.take_output(), b"ab\0");
+            }
+
+            #[test]
+            fn runtime_error() {
+                let interpreter = load(Configuration::default(), b"+.<");
+                let mut machine = Machine::new(&interpreter);
+                match machine.run(u64::MAX).unwrap_err().without_location() {
+                    RuntimeError::CellUnderflow => {},
+                    _ => unreachable!(),
+                }
+                assert_eq!(machine.take_output(), b"\x01");
+                assert_eq!(machine.instruction(), 2);
+            }
+
+            #[test]
+            fn max_steps() {
+                let configuration = Configuration {
+                    max_steps: Some(2),
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration, b"+>+");
+                let mut machine = Machine::new(&interpreter);
+                assert_eq!(machine.run(2).unwrap(), Status::Paused);
+                match machine.run(1).unwrap_err().without_location() {
+                    RuntimeError::FuelExhausted(2) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn timeout() {
+                let configuration = Configuration {
+                    timeout: Some(Duration::from_millis(10)),
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration, b"+[]");
+                let mut machine = Machine::new(&interpreter);
+                // The deadline holds across the slices, even though none of them reaches it
+                // alone.
+                let error = loop {
+                    match machine.run(1000) {
+                        Ok(status) => assert_eq!(status, Status::Paused),
+                        Err(error) => break error,
+                    }
+                };
+                match error.without_location() {
+                    RuntimeError::TimedOut(steps) => assert!(*steps > 0),
+                    _ => unreachable!(),
+                }
+            }
+        }
+    }
+}
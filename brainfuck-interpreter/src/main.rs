@@ -1,39 +1,221 @@
-use brainfuck_interpreter::Interpreter;
+use brainfuck_interpreter::{
+    ArithmeticPolicy,
+    CellWidth,
+    CellWrite,
+    Configuration,
+    EndOfInputPolicy,
+    FormatOptions,
+    FormatStyle,
+    Interpreter,
+    MEMORY_SIZE,
+    Tape,
+    TapeGrowth,
+    Target,
+    Trace,
+    error::{
+        RuntimeError,
+        SyntaxError,
+    },
+    io_error_to_json,
+};
 use clap::{
     App,
+    AppSettings,
     Arg,
+    ArgMatches,
+    SubCommand,
 };
 use colored::*;
 use memmap::Mmap;
 use std::{
     convert::TryFrom,
-    fs::OpenOptions,
+    env,
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io::{
+        self,
+        BufReader,
+        BufWriter,
+        IsTerminal,
+        Read,
+        Write,
+    },
+    path::Path,
     process,
+    time::Duration,
 };
 
-fn main() {
-    let args = App::new("brainfuck-interpreter")
-        .version("1.0.0")
-        .about("A simple Brainfuck interpreter written in Rust")
-        .author("Umut S. <umutsahin@protonmail.com>")
-        .arg(
-            Arg::with_name("SCRIPT")
-                .help("Sets the script to execute")
-                .index(1)
-                .required(true),
-        )
-        .get_matches();
+fn configuration_args(memory_size: &str) -> Vec<Arg<'_, '_>> {
+    vec![
+        Arg::with_name("TAPE_SIZE")
+            .help("Sets the initial number of cells on the tape")
+            .long("tape-size")
+            .takes_value(true)
+            .default_value(memory_size)
+            .validator(|value| {
+                match value.parse::<usize>() {
+                    Ok(size) if size > 0 => Ok(()),
+                    _ => Err(String::from("tape size must be a positive integer")),
+                }
+            }),
+        Arg::with_name("CELL_WIDTH")
+            .help("Sets the number of bits in a cell")
+            .long("cell-width")
+            .takes_value(true)
+            .possible_values(&["8", "16", "32"])
+            .default_value("8"),
+        Arg::with_name("TAPE_GROWTH")
+            .help("Sets the directions, which the tape grows in on demand")
+            .long("tape-growth")
+            .takes_value(true)
+            .possible_values(&["fixed", "right", "both"])
+            .default_value("fixed"),
+        Arg::with_name("ARITHMETIC")
+            .help("Sets what happens when a cell goes below zero or above its maximum value")
+            .long("arithmetic")
+            .takes_value(true)
+            .possible_values(&["wrap", "saturate", "trap"])
+            .default_value("wrap"),
+        Arg::with_name("EOF")
+            .help("Sets what the ',' instruction stores when there is no more input")
+            .long("eof")
+            .takes_value(true)
+            .possible_values(&["zero", "max", "unchanged"])
+            .default_value("zero"),
+        Arg::with_name("OPTIMIZE")
+            .help("Optimizes the program before executing it")
+            .short("O")
+            .long("optimize"),
+        extensions_arg(),
+    ]
+}
+
+fn extensions_arg() -> Arg<'static, 'static> {
+    Arg::with_name("EXTENSIONS")
+        .help("Enables '#' to dump the tape and '!' to embed the input into the script")
+        .long("extensions")
+}
+
+fn message_format_arg() -> Arg<'static, 'static> {
+    Arg::with_name("MESSAGE_FORMAT")
+        .help("Sets the format of the error messages")
+        .long("message-format")
+        .takes_value(true)
+        .possible_values(&["human", "json"])
+        .default_value("human")
+}
+
+fn configuration(args: &ArgMatches) -> Configuration {
+    let mut configuration = Configuration::default();
+    if let Some(tape_size) = args.value_of("TAPE_SIZE") {
+        configuration.tape_size = tape_size.parse().unwrap();
+    }
+    if let Some(cell_width) = args.value_of("CELL_WIDTH") {
+        configuration.cell_width = match cell_width {
+            "8" => CellWidth::U8,
+            "16" => CellWidth::U16,
+            "32" => CellWidth::U32,
+            _ => unreachable!(),
+        };
+    }
+    if let Some(tape_growth) = args.value_of("TAPE_GROWTH") {
+        configuration.tape_growth = match tape_growth {
+            "fixed" => TapeGrowth::Fixed,
+            "right" => TapeGrowth::Right,
+            "both" => TapeGrowth::Both,
+            _ => unreachable!(),
+        };
+    }
+    if let Some(arithmetic) = args.value_of("ARITHMETIC") {
+        configuration.arithmetic = match arithmetic {
+            "wrap" => ArithmeticPolicy::Wrap,
+            "saturate" => ArithmeticPolicy::Saturate,
+            "trap" => ArithmeticPolicy::Trap,
+            _ => unreachable!(),
+        };
+    }
+    if let Some(end_of_input) = args.value_of("EOF") {
+        configuration.end_of_input = match end_of_input {
+            "zero" => EndOfInputPolicy::Zero,
+            "max" => EndOfInputPolicy::Max,
+            "unchanged" => EndOfInputPolicy::Unchanged,
+            _ => unreachable!(),
+        };
+    }
+    if let Some(max_steps) = args.value_of("MAX_STEPS") {
+        configuration.max_steps = Some(max_steps.parse().unwrap());
+    }
+    if let Some(timeout) = args.value_of("TIMEOUT") {
+        configuration.timeout = Some(Duration::from_secs_f64(timeout.parse().unwrap()));
+    }
+    configuration.optimize = args.is_present("OPTIMIZE");
+    configuration.extensions = args.is_present("EXTENSIONS");
+    configuration
+}
+
+// Scripts given as files are mapped into memory, and the others are read into a
+// buffer.
+enum Script {
+    Mapped(Mmap),
+    Buffered(Vec<u8>),
+}
+
+impl Script {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Script::Mapped(script_file_in_memory) => &script_file_in_memory[..],
+            Script::Buffered(script) => script,
+        }
+    }
+}
+
+fn script_name<'a>(args: &'a ArgMatches) -> &'a str {
+    if args.is_present("EVAL") {
+        "<eval>"
+    } else {
+        match args.value_of("SCRIPT").unwrap() {
+            "-" => "<stdin>",
+            script => script,
+        }
+    }
+}
+
+fn read_script(args: &ArgMatches) -> Script {
+    if let Some(code) = args.value_of("EVAL") {
+        return Script::Buffered(code.as_bytes().to_vec());
+    }
+
+    if args.value_of("SCRIPT") == Some("-") {
+        let mut script = Vec::new();
+        if let Err(error) = io::stdin().read_to_end(&mut script) {
+            report_io_error(
+                args,
+                "io error:",
+                "unable to read the",
+                "SCRIPT",
+                "from stdin",
+                &error,
+            );
+            process::exit(1);
+        }
+        return Script::Buffered(script);
+    }
 
     let script_file = OpenOptions::new()
         .read(true)
         .write(false)
         .open(args.value_of("SCRIPT").unwrap())
         .unwrap_or_else(|error| {
-            eprintln!(
-                "{} unable to open the {} file ({})",
-                "io error:".red().bold(),
-                "SCRIPT".red().bold(),
-                error,
+            report_io_error(
+                args,
+                "io error:",
+                "unable to open the",
+                "SCRIPT",
+                "file",
+                &error,
             );
             process::exit(1);
         });
@@ -41,11 +223,13 @@ fn main() {
     let script_file_in_memory = unsafe {
         match Mmap::map(&script_file) {
             Err(error) => {
-                eprintln!(
-                    "{} unable to map the provided {} file into memory ({})",
-                    "memory mapping error:".red().bold(),
-                    "SCRIPT".red().bold(),
-                    error,
+                report_io_error(
+                    args,
+                    "memory mapping error:",
+                    "unable to map the provided",
+                    "SCRIPT",
+                    "file into memory",
+                    &error,
                 );
                 drop(script_file);
                 process::exit(1);
@@ -54,21 +238,683 @@ fn main() {
         }
     };
 
-    let interpreter = match Interpreter::try_from(&script_file_in_memory[..]) {
+    // The mapping stays valid after the file is closed.
+    drop(script_file);
+    Script::Mapped(script_file_in_memory)
+}
+
+fn report_io_error(
+    args: &ArgMatches,
+    label: &str,
+    action: &str,
+    name: &str,
+    rest: &str,
+    error: &io::Error,
+) {
+    if args.value_of("MESSAGE_FORMAT") == Some("json") {
+        let message = format!("{} {} {} ({})", action, name, rest, error);
+        eprintln!("{}", io_error_to_json(&message, script_name(args)));
+    } else {
+        eprintln!(
+            "{} {} {} {} ({})",
+            label.red().bold(),
+            action,
+            name.red().bold(),
+            rest,
+            error,
+        );
+    }
+}
+
+fn report_syntax_errors(args: &ArgMatches, errors: &[SyntaxError], program: &[u8]) {
+    let script = script_name(args);
+    if args.value_of("MESSAGE_FORMAT") == Some("json") {
+        for error in errors {
+            eprintln!("{}", error.to_json(script));
+        }
+    } else {
+        let diagnostics = errors
+            .iter()
+            .map(|error| error.render(script, program))
+            .collect::<Vec<_>>();
+        eprintln!("{}", diagnostics.join("\n\n"));
+    }
+}
+
+fn report_runtime_error(args: &ArgMatches, error: &RuntimeError, program: &[u8]) {
+    let script = script_name(args);
+    if args.value_of("MESSAGE_FORMAT") == Some("json") {
+        eprintln!("{}", error.to_json(script));
+    } else {
+        eprintln!("{}", error.render(script, program));
+    }
+}
+
+fn execute(
+    args: &ArgMatches,
+    interpreter: &Interpreter,
+    script: &[u8],
+    input: Box<dyn Read>,
+    trace: Option<File>,
+) -> Result<Tape, RuntimeError> {
+    let stdout = io::stdout();
+    let output = stdout.lock();
+
+    #[cfg(feature = "jit")]
+    {
+        if args.is_present("JIT") {
+            return interpreter.execute_jit_with(input, output);
+        }
+    }
+
+    if args.is_present("PROFILE") {
+        // The report goes to stderr, so it doesn't mix with the output of the script.
+        let (tape, profile) = interpreter.profile_with(input, output)?;
+        eprint!("{}", profile.render(script));
+        Ok(tape)
+    } else if let Some(trace) = trace {
+        interpreter.trace_with(input, output, BufWriter::new(trace))
+    } else {
+        interpreter.execute_into_tape_with(input, output)
+    }
+}
+
+fn run(args: &ArgMatches) {
+    // The raw bytes of the tape would be mixed with the errors on stderr, so they
+    // are only written to a file.
+    if args.value_of("DUMP_TAPE") == Some("binary") && !args.is_present("DUMP_TAPE_OUTPUT") {
+        clap::Error::with_description(
+            "The binary tape dump requires '--dump-tape-output <DUMP_TAPE_OUTPUT>'",
+            clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit();
+    }
+
+    let script = read_script(args);
+    let mut interpreter = Interpreter::with_configuration(configuration(args));
+
+    // Only the scripts given as files are cached, as the others have nowhere to put
+    // the cache.
+    let cache = match script {
+        Script::Mapped(..) if args.is_present("CACHE") => {
+            Some(Path::new(script_name(args)).with_extension("bfc"))
+        },
+        _ => None,
+    };
+    let loaded_from_cache = cache
+        .as_ref()
+        .and_then(|cache| fs::read(cache).ok())
+        .map(|bytecode| interpreter.load_bytecode(&bytecode, script.bytes()).is_ok())
+        .unwrap_or(false);
+
+    if !loaded_from_cache {
+        if let Err(errors) = interpreter.load_collecting_errors(script.bytes()) {
+            let (program, _) = interpreter.split_script(script.bytes());
+            report_syntax_errors(args, &errors, program);
+            drop(interpreter);
+            drop(script);
+            process::exit(1);
+        }
+
+        if let Some(cache) = cache {
+            let bytecode = interpreter.save_bytecode(script.bytes());
+            if let Err(error) = fs::write(cache, bytecode) {
+                eprintln!(
+                    "{} unable to write the {} file ({})",
+                    "warning:".yellow().bold(),
+                    "CACHE".yellow().bold(),
+                    error,
+                );
+            }
+        }
+    }
+
+    let trace = match args.value_of("TRACE").map(File::create).transpose() {
+        Ok(trace) => trace,
+        Err(error) => {
+            report_io_error(
+                args,
+                "io error:",
+                "unable to create the",
+                "TRACE",
+                "file",
+                &error,
+            );
+            drop(interpreter);
+            drop(script);
+            process::exit(1);
+        },
+    };
+
+    let input: Box<dyn Read> = match args.value_of("INPUT").map(File::open).transpose() {
+        Ok(Some(input)) => Box::new(BufReader::new(input)),
+        Ok(None) => Box::new(io::stdin().lock()),
+        Err(error) => {
+            report_io_error(
+                args,
+                "io error:",
+                "unable to open the",
+                "INPUT",
+                "file",
+                &error,
+            );
+            drop(trace);
+            drop(interpreter);
+            drop(script);
+            process::exit(1);
+        },
+    };
+
+    let tape = match execute(args, &interpreter, script.bytes(), input, trace) {
+        Ok(tape) => tape,
         Err(error) => {
-            eprintln!("{}", error);
-            drop(script_file_in_memory);
-            drop(script_file);
+            let (program, _) = interpreter.split_script(script.bytes());
+            report_runtime_error(args, &error, program);
+            drop(interpreter);
+            drop(script);
+            process::exit(1);
+        },
+    };
+
+    if let Some(format) = args.value_of("DUMP_TAPE") {
+        let dump = match format {
+            "hex" => tape.to_hex().into_bytes(),
+            "binary" => tape.to_bytes(),
+            "json" => format!("{}\n", tape.to_json()).into_bytes(),
+            _ => unreachable!(),
+        };
+        let result = match args.value_of("DUMP_TAPE_OUTPUT") {
+            None => io::stderr().write_all(&dump),
+            Some(output) => fs::write(output, dump),
+        };
+        if let Err(error) = result {
+            report_io_error(
+                args,
+                "io error:",
+                "unable to write the",
+                "DUMP_TAPE_OUTPUT",
+                "file",
+                &error,
+            );
+            drop(interpreter);
+            drop(script);
             process::exit(1);
+        }
+    }
+}
+
+fn load_script(args: &ArgMatches) -> (Script, Interpreter) {
+    let script = read_script(args);
+
+    let mut interpreter = Interpreter::with_configuration(configuration(args));
+    if let Err(errors) = interpreter.load_collecting_errors(script.bytes()) {
+        let (program, _) = interpreter.split_script(script.bytes());
+        report_syntax_errors(args, &errors, program);
+        drop(interpreter);
+        drop(script);
+        process::exit(1);
+    }
+
+    (script, interpreter)
+}
+
+fn compile(args: &ArgMatches) {
+    let (script, interpreter) = load_script(args);
+
+    let target = match args.value_of("TARGET").unwrap() {
+        "c" => Target::C,
+        "rust" => Target::Rust,
+        _ => unreachable!(),
+    };
+    let source = interpreter.compile(target);
+
+    let result = match args.value_of("OUTPUT") {
+        None => io::stdout().write_all(source.as_bytes()),
+        Some(output) => fs::write(output, &source),
+    };
+    if let Err(error) = result {
+        report_io_error(
+            args,
+            "io error:",
+            "unable to write the",
+            "OUTPUT",
+            "file",
+            &error,
+        );
+        drop(interpreter);
+        drop(script);
+        process::exit(1);
+    }
+}
+
+fn debug(args: &ArgMatches) {
+    let (script, interpreter) = load_script(args);
+
+    if let Err(error) = interpreter.debug() {
+        // The errors, which halt the program, are already reported in the session,
+        // so only the errors of the session itself are reported here.
+        if let RuntimeError::Io(_) = error {
+            let (program, _) = interpreter.split_script(script.bytes());
+            report_runtime_error(args, &error, program);
+        }
+        drop(interpreter);
+        drop(script);
+        process::exit(1);
+    }
+}
+
+fn fmt(args: &ArgMatches) {
+    let (script, interpreter) = load_script(args);
+
+    let options = FormatOptions {
+        style: if args.is_present("MINIFY") {
+            FormatStyle::Minified
+        } else {
+            FormatStyle::Pretty
         },
-        Ok(interpreter) => interpreter,
+        line_width: args.value_of("LINE_WIDTH").unwrap().parse().unwrap(),
+        keep_comments: args.is_present("KEEP_COMMENTS"),
+    };
+    let formatted = interpreter.format(script.bytes(), &options);
+
+    if args.is_present("CHECK") {
+        if formatted != script.bytes() {
+            eprintln!(
+                "{} {} is not formatted",
+                "check error:".red().bold(),
+                script_name(args)
+            );
+            drop(interpreter);
+            drop(script);
+            process::exit(1);
+        }
+        return;
+    }
+
+    let result = match args.value_of("OUTPUT") {
+        None => io::stdout().write_all(&formatted),
+        Some(output) => fs::write(output, &formatted),
     };
+    if let Err(error) = result {
+        report_io_error(
+            args,
+            "io error:",
+            "unable to write the",
+            "OUTPUT",
+            "file",
+            &error,
+        );
+        drop(interpreter);
+        drop(script);
+        process::exit(1);
+    }
+}
 
-    if let Err(error) = interpreter.execute() {
+fn repl(args: &ArgMatches) {
+    // Errors of the entries are reported by the REPL itself, so only io errors end
+    // up here.
+    let interpreter = Interpreter::with_configuration(configuration(args));
+    if let Err(error) = interpreter.repl() {
         eprintln!("{}", error);
         drop(interpreter);
-        drop(script_file_in_memory);
-        drop(script_file);
         process::exit(1);
     }
 }
+
+fn describe_step(trace: &Trace, step: usize) -> String {
+    let instruction = trace.steps()[step].instruction;
+    format!(
+        "step {}: '{}' at {}",
+        step + 1,
+        trace.instruction(instruction),
+        trace.location(instruction),
+    )
+}
+
+fn describe_write(trace: &Trace, step: usize, write: CellWrite) -> String {
+    format!(
+        "{} changed cell {} from {} to {}",
+        describe_step(trace, step),
+        write.cell,
+        write.before,
+        write.after,
+    )
+}
+
+fn print_step(trace: &Trace, step: usize) {
+    println!(
+        "{} with the head at cell {}",
+        describe_step(trace, step),
+        trace.steps()[step].head,
+    );
+    for write in &trace.steps()[step].writes {
+        println!("  cell {}: {} -> {}", write.cell, write.before, write.after);
+    }
+}
+
+fn replay(args: &ArgMatches) {
+    let trace = fs::read(args.value_of("TRACE").unwrap()).unwrap_or_else(|error| {
+        eprintln!(
+            "{} unable to read the {} file ({})",
+            "io error:".red().bold(),
+            "TRACE".red().bold(),
+            error,
+        );
+        process::exit(1);
+    });
+    let trace = Trace::try_from(&trace[..]).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(1);
+    });
+
+    let mut queried = false;
+    if let Some(step) = args.value_of("STEP") {
+        let step = step.parse::<usize>().unwrap();
+        if step == 0 || step > trace.steps().len() {
+            println!("the trace doesn't contain step {}", step);
+        } else {
+            print_step(&trace, step - 1);
+        }
+        queried = true;
+    }
+    if let Some(cell) = args.value_of("CELL") {
+        let cell = cell.parse::<isize>().unwrap();
+        let writes = if args.is_present("HISTORY") {
+            trace.writes(cell)
+        } else {
+            trace.last_write(cell).into_iter().collect()
+        };
+        if writes.is_empty() {
+            println!("cell {} was never written", cell);
+        }
+        for (step, write) in writes {
+            println!("{}", describe_write(&trace, step, write));
+        }
+        queried = true;
+    }
+
+    if !queried {
+        println!("the trace contains {} steps", trace.steps().len());
+        if !trace.steps().is_empty() {
+            print_step(&trace, trace.steps().len() - 1);
+        }
+    }
+}
+
+fn main() {
+    // Colors are only meant for humans looking at a terminal (see https://no-color.org).
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if no_color || !io::stderr().is_terminal() {
+        colored::control::set_override(false);
+    }
+
+    let memory_size = MEMORY_SIZE.to_string();
+    let line_width = FormatOptions::default().line_width.to_string();
+    let app = App::new("brainfuck-interpreter")
+        .version("1.0.0")
+        .about("A simple Brainfuck interpreter written in Rust")
+        .author("Umut S. <umutsahin@protonmail.com>")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .arg(
+            Arg::with_name("SCRIPT")
+                .help("Sets the script to execute ('-' to read it from stdin)")
+                .index(1)
+                .required_unless("EVAL"),
+        )
+        .arg(
+            Arg::with_name("EVAL")
+                .help("Sets the code to execute instead of a script")
+                .short("e")
+                .long("eval")
+                .takes_value(true)
+                .allow_hyphen_values(true)
+                .conflicts_with("SCRIPT"),
+        )
+        .arg(
+            Arg::with_name("INPUT")
+                .help("Sets the file to read the input of the script from [default: stdin]")
+                .long("input")
+                .takes_value(true),
+        )
+        .args(&configuration_args(&memory_size))
+        .arg(message_format_arg())
+        .arg(
+            Arg::with_name("MAX_STEPS")
+                .help("Sets the maximum number of instructions to execute")
+                .long("max-steps")
+                .takes_value(true)
+                .validator(|value| {
+                    match value.parse::<u64>() {
+                        Ok(_) => Ok(()),
+                        _ => Err(String::from("max steps must be a non-negative integer")),
+                    }
+                }),
+        )
+        .arg(
+            Arg::with_name("TIMEOUT")
+                .help("Sets the maximum number of seconds to execute the script for")
+                .long("timeout")
+                .takes_value(true)
+                .validator(|value| {
+                    match value.parse::<f64>().map(Duration::try_from_secs_f64) {
+                        Ok(Ok(_)) => Ok(()),
+                        _ => {
+                            Err(String::from(
+                                "timeout must be a non-negative number of seconds",
+                            ))
+                        },
+                    }
+                }),
+        )
+        .arg(
+            Arg::with_name("PROFILE")
+                .help("Prints the instruction count and the hottest loops to stderr at the end")
+                .long("profile"),
+        )
+        .arg(
+            Arg::with_name("TRACE")
+                .help("Records every executed instruction to the file for the replay subcommand")
+                .long("trace")
+                .takes_value(true)
+                .conflicts_with("PROFILE"),
+        )
+        .arg(
+            Arg::with_name("DUMP_TAPE")
+                .help("Writes the final tape up to the last cell the head reached in the format")
+                .long("dump-tape")
+                .takes_value(true)
+                .possible_values(&["hex", "binary", "json"]),
+        )
+        .arg(
+            Arg::with_name("DUMP_TAPE_OUTPUT")
+                .help("Sets the file to write the tape to (required for binary) [default: stderr]")
+                .long("dump-tape-output")
+                .takes_value(true)
+                .requires("DUMP_TAPE"),
+        )
+        .arg(
+            Arg::with_name("CACHE")
+                .help("Caches the loaded program next to the script and reuses it when possible")
+                .long("cache"),
+        )
+        .subcommand(
+            SubCommand::with_name("compile")
+                .about("Compiles the script ahead of time")
+                .arg(
+                    Arg::with_name("SCRIPT")
+                        .help("Sets the script to compile ('-' to read it from stdin)")
+                        .index(1)
+                        .required_unless("EVAL"),
+                )
+                .arg(
+                    Arg::with_name("EVAL")
+                        .help("Sets the code to compile instead of a script")
+                        .short("e")
+                        .long("eval")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .conflicts_with("SCRIPT"),
+                )
+                .arg(
+                    Arg::with_name("TARGET")
+                        .help("Sets the language to compile the script to")
+                        .long("target")
+                        .takes_value(true)
+                        .possible_values(&["c", "rust"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .help("Sets the file to write the compiled script to [default: stdout]")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true),
+                )
+                .args(&configuration_args(&memory_size))
+                .arg(message_format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("debug")
+                .about("Executes the script step by step in an interactive debugger")
+                .arg(
+                    Arg::with_name("SCRIPT")
+                        .help("Sets the script to debug ('-' to read it from stdin)")
+                        .index(1)
+                        .required_unless("EVAL"),
+                )
+                .arg(
+                    Arg::with_name("EVAL")
+                        .help("Sets the code to debug instead of a script")
+                        .short("e")
+                        .long("eval")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .conflicts_with("SCRIPT"),
+                )
+                .args(&configuration_args(&memory_size))
+                .arg(message_format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats the script with one loop body per indentation level or minifies it")
+                .arg(
+                    Arg::with_name("SCRIPT")
+                        .help("Sets the script to format ('-' to read it from stdin)")
+                        .index(1)
+                        .required_unless("EVAL"),
+                )
+                .arg(
+                    Arg::with_name("EVAL")
+                        .help("Sets the code to format instead of a script")
+                        .short("e")
+                        .long("eval")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .conflicts_with("SCRIPT"),
+                )
+                .arg(
+                    Arg::with_name("MINIFY")
+                        .help("Strips everything but the commands instead of indenting the loops")
+                        .long("minify")
+                        .conflicts_with("KEEP_COMMENTS"),
+                )
+                .arg(
+                    Arg::with_name("LINE_WIDTH")
+                        .help("Sets the maximum width of the lines of commands")
+                        .long("line-width")
+                        .takes_value(true)
+                        .default_value(&line_width)
+                        .validator(|value| {
+                            match value.parse::<usize>() {
+                                Ok(width) if width > 0 => Ok(()),
+                                _ => Err(String::from("line width must be a positive integer")),
+                            }
+                        }),
+                )
+                .arg(
+                    Arg::with_name("KEEP_COMMENTS")
+                        .help("Keeps the comments, each of their lines on a line of its own")
+                        .long("keep-comments"),
+                )
+                .arg(
+                    Arg::with_name("CHECK")
+                        .help("Fails instead of printing if the script isn't formatted already")
+                        .long("check")
+                        .conflicts_with("OUTPUT"),
+                )
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .help("Sets the file to write the formatted script to [default: stdout]")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true),
+                )
+                .arg(extensions_arg())
+                .arg(message_format_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("repl")
+                .about("Executes the lines entered interactively on a tape persisting across them")
+                .args(&configuration_args(&memory_size)),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Answers questions about a recorded trace without executing the script")
+                .arg(
+                    Arg::with_name("TRACE")
+                        .help("Sets the trace to replay")
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("CELL")
+                        .help("Prints the last step, which wrote the cell")
+                        .long("cell")
+                        .takes_value(true)
+                        .allow_hyphen_values(true)
+                        .validator(|value| {
+                            match value.parse::<isize>() {
+                                Ok(_) => Ok(()),
+                                _ => Err(String::from("cell must be an integer")),
+                            }
+                        }),
+                )
+                .arg(
+                    Arg::with_name("HISTORY")
+                        .help("Prints every step, which wrote the cell, instead of the last one")
+                        .long("history")
+                        .requires("CELL"),
+                )
+                .arg(
+                    Arg::with_name("STEP")
+                        .help("Prints the instruction executed at the step and the cells it wrote")
+                        .long("step")
+                        .takes_value(true)
+                        .validator(|value| {
+                            match value.parse::<usize>() {
+                                Ok(_) => Ok(()),
+                                _ => Err(String::from("step must be a non-negative integer")),
+                            }
+                        }),
+                ),
+        );
+
+    #[cfg(feature = "jit")]
+    let app = app.arg(
+        Arg::with_name("JIT")
+            .help("Compiles the script to machine code just in time before executing it")
+            .long("jit")
+            .conflicts_with_all(&["PROFILE", "TRACE"]),
+    );
+
+    let args = app.get_matches();
+
+    match args.subcommand() {
+        ("compile", Some(args)) => compile(args),
+        ("debug", Some(args)) => debug(args),
+        ("fmt", Some(args)) => fmt(args),
+        ("repl", Some(args)) => repl(args),
+        ("replay", Some(args)) => replay(args),
+        _ => run(&args),
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        Configuration,
+    },
+    instruction::Instruction,
+    span::Span,
+};
+use std::convert::TryFrom;
+
+pub(crate) fn optimize(
+    program: Vec<Instruction>,
+    spans: Vec<Span>,
+    configuration: &Configuration,
+) -> (Vec<Instruction>, Vec<Span>) {
+    let (program, spans) = replace_loop_idioms(program, spans, configuration);
+    let (mut program, mut spans) = address_by_offset(program, spans);
+    link_loops(&mut program);
+    program.shrink_to_fit();
+    spans.shrink_to_fit();
+    (program, spans)
+}
+
+// An idiom spans its whole loop.
+fn replace_loop_idioms(
+    program: Vec<Instruction>,
+    spans: Vec<Span>,
+    configuration: &Configuration,
+) -> (Vec<Instruction>, Vec<Span>) {
+    let mut optimized_program = Vec::with_capacity(program.len());
+    let mut optimized_spans = Vec::with_capacity(spans.len());
+
+    let mut current_instruction_index = 0;
+    while current_instruction_index != program.len() {
+        if let Instruction::StartLoop(end_of_loop) = program[current_instruction_index] {
+            let body = &program[current_instruction_index + 1..end_of_loop];
+            if let Some(idiom) = loop_idiom(body, configuration) {
+                optimized_program.push(idiom);
+                optimized_spans.push(spans[current_instruction_index].to(spans[end_of_loop]));
+                current_instruction_index = end_of_loop + 1;
+                continue;
+            }
+        }
+        optimized_program.push(program[current_instruction_index].clone());
+        optimized_spans.push(spans[current_instruction_index]);
+        current_instruction_index += 1;
+    }
+    (optimized_program, optimized_spans)
+}
+
+// Pointer movements within a basic block are deferred to the end of the block,
+// and the arithmetic and io instructions in between address their cells
+// relative to the head instead. Since adjacent movements are already folded by
+// the loader, every cell the original block moves onto is either accessed or
+// the final position, so bounds errors and tape growth happen at the same
+// cells. The deferred movement spans from the first movement it replaces to the
+// last one.
+fn address_by_offset(program: Vec<Instruction>, spans: Vec<Span>) -> (Vec<Instruction>, Vec<Span>) {
+    let mut optimized_program = Vec::with_capacity(program.len());
+    let mut optimized_spans = Vec::with_capacity(spans.len());
+
+    let mut offset = 0isize;
+    let mut movement_span: Option<Span> = None;
+    for (instruction, span) in program.into_iter().zip(spans) {
+        let addressed_instruction = match instruction {
+            Instruction::MoveLeft(amount) => {
+                offset -= amount as isize;
+                movement_span = Some(movement_span.map_or(span, |first| first.to(span)));
+                continue;
+            },
+            Instruction::MoveRight(amount) => {
+                offset += amount as isize;
+                movement_span = Some(movement_span.map_or(span, |first| first.to(span)));
+                continue;
+            },
+            Instruction::Increment(amount) if offset != 0 => {
+                Instruction::IncrementAt(offset, amount)
+            },
+            Instruction::Decrement(amount) if offset != 0 => {
+                Instruction::DecrementAt(offset, amount)
+            },
+            Instruction::Read if offset != 0 => Instruction::ReadAt(offset),
+            Instruction::Write if offset != 0 => Instruction::WriteAt(offset),
+            Instruction::Increment(_) |
+            Instruction::Decrement(_) |
+            Instruction::Read |
+            Instruction::Write => instruction,
+            _ => {
+                if let Some(movement_span) = movement_span.take() {
+                    if offset != 0 {
+                        optimized_program.push(Instruction::movement(offset));
+                        optimized_spans.push(movement_span);
+                    }
+                    offset = 0;
+                }
+                instruction
+            },
+        };
+        optimized_program.push(addressed_instruction);
+        optimized_spans.push(span);
+    }
+    if let Some(movement_span) = movement_span {
+        if offset != 0 {
+            optimized_program.push(Instruction::movement(offset));
+            optimized_spans.push(movement_span);
+        }
+    }
+
+    (optimized_program, optimized_spans)
+}
+
+pub(crate) fn link_loops(program: &mut [Instruction]) {
+    let mut loop_balancer = Vec::new();
+    for current_instruction_index in 0..program.len() {
+        match program[current_instruction_index] {
+            Instruction::StartLoop(_) => {
+                loop_balancer.push(current_instruction_index);
+            },
+            Instruction::EndLoop(_) => {
+                let start_of_loop = loop_balancer.pop().unwrap();
+                program[start_of_loop] = Instruction::StartLoop(current_instruction_index);
+                program[current_instruction_index] = Instruction::EndLoop(start_of_loop);
+            },
+            _ => {},
+        }
+    }
+}
+
+fn loop_idiom(body: &[Instruction], configuration: &Configuration) -> Option<Instruction> {
+    let wrapping = configuration.arithmetic == ArithmeticPolicy::Wrap;
+    match body {
+        [Instruction::Decrement(1)] => Some(Instruction::SetZero),
+        [Instruction::Increment(1)] if wrapping => Some(Instruction::SetZero),
+        [Instruction::MoveLeft(amount)] => Some(Instruction::ScanLeft(*amount)),
+        [Instruction::MoveRight(amount)] => Some(Instruction::ScanRight(*amount)),
+        _ if wrapping => multiply_add(body),
+        _ => None,
+    }
+}
+
+// Every cell the body moves onto becomes a target (even if its factor ends up
+// as zero), and targets are kept in the order they are first visited. This way,
+// the bounds of the tape are checked (or the tape is grown) exactly like the
+// first iteration of the original loop would do.
+fn multiply_add(body: &[Instruction]) -> Option<Instruction> {
+    let mut position = 0isize;
+    let mut counter = 0isize;
+    let mut targets: Vec<(isize, isize)> = Vec::new();
+
+    for instruction in body {
+        let amount = match *instruction {
+            Instruction::MoveLeft(amount) | Instruction::MoveRight(amount) => {
+                let amount = isize::try_from(amount).ok()?;
+                position = if let Instruction::MoveLeft(_) = instruction {
+                    position.checked_sub(amount)?
+                } else {
+                    position.checked_add(amount)?
+                };
+                if position != 0 && targets.iter().all(|&(offset, _)| offset != position) {
+                    targets.push((position, 0));
+                }
+                continue;
+            },
+            Instruction::Increment(amount) => isize::try_from(amount).ok()?,
+            Instruction::Decrement(amount) => -isize::try_from(amount).ok()?,
+            _ => return None,
+        };
+
+        if position == 0 {
+            counter += amount;
+        } else {
+            let target = targets
+                .iter_mut()
+                .find(|(offset, _)| *offset == position)
+                .unwrap();
+            target.1 = target.1.wrapping_add(amount);
+        }
+    }
+
+    if position != 0 || counter != -1 || targets.is_empty() {
+        return None;
+    }
+    Some(Instruction::MultiplyAdd(targets))
+}
+
+#[cfg(test)]
+mod functions {
+    mod optimize {
+        use crate::{
+            configuration::{
+                ArithmeticPolicy,
+                Configuration,
+            },
+            instruction::Instruction,
+            interpreter::Interpreter,
+            span::{
+                Location,
+                Span,
+            },
+        };
+
+        fn optimize(script: &[u8], arithmetic: ArithmeticPolicy) -> Interpreter {
+            let mut interpreter = Interpreter::with_configuration(Configuration {
+                arithmetic,
+                optimize: true,
+                ..Configuration::default()
+            });
+            interpreter.load(script).unwrap();
+            interpreter
+        }
+
+        fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+            Span::new(Location::new(start.0, start.1), Location::new(end.0, end.1))
+        }
+
+        #[test]
+        fn set_zero() {
+            let interpreter = optimize(b"+[-]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::Increment(1), Instruction::SetZero],
+            );
+
+            let interpreter = optimize(b"[+]", ArithmeticPolicy::Wrap);
+            assert_eq!(interpreter.program(), &[Instruction::SetZero]);
+
+            let interpreter = optimize(b"[-]", ArithmeticPolicy::Trap);
+            assert_eq!(interpreter.program(), &[Instruction::SetZero]);
+        }
+
+        #[test]
+        fn set_zero_is_not_applied_when_it_would_change_the_behavior() {
+            let interpreter = optimize(b"[+]", ArithmeticPolicy::Saturate);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::StartLoop(2),
+                    Instruction::Increment(1),
+                    Instruction::EndLoop(0)
+                ],
+            );
+
+            let interpreter = optimize(b"[--]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::StartLoop(2),
+                    Instruction::Decrement(2),
+                    Instruction::EndLoop(0)
+                ],
+            );
+        }
+
+        #[test]
+        fn scan() {
+            let interpreter = optimize(b"[<][>>>]", ArithmeticPolicy::Trap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::ScanLeft(1), Instruction::ScanRight(3)],
+            );
+        }
+
+        #[test]
+        fn multiply_add() {
+            let interpreter = optimize(b"[->+>++<<]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::MultiplyAdd(vec![(1, 1), (2, 2)])]
+            );
+
+            let interpreter = optimize(b"[>>-<<-<+>]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::MultiplyAdd(vec![(2, -1), (-1, 1)])]
+            );
+
+            let interpreter = optimize(b"[->>+<+<]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::MultiplyAdd(vec![(2, 1), (1, 1)])]
+            );
+
+            let interpreter = optimize(b"[->+>+<-<]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::MultiplyAdd(vec![(1, 0), (2, 1)])]
+            );
+        }
+
+        #[test]
+        fn multiply_add_is_not_applied_when_it_would_change_the_behavior() {
+            let contains_multiply_add = |interpreter: &Interpreter| {
+                interpreter
+                    .program()
+                    .iter()
+                    .any(|instruction| matches!(instruction, Instruction::MultiplyAdd(_)))
+            };
+
+            for &arithmetic in &[ArithmeticPolicy::Saturate, ArithmeticPolicy::Trap] {
+                assert!(!contains_multiply_add(&optimize(b"[->+<]", arithmetic)));
+            }
+            let scripts: &[&[u8]] = &[
+                b"[-->+<]",
+                b"[+>+<]",
+                b"[->+]",
+                b"[>+<]",
+                b"[->+<.]",
+                b"[->[-]<]",
+            ];
+            for script in scripts {
+                assert!(!contains_multiply_add(&optimize(
+                    script,
+                    ArithmeticPolicy::Wrap
+                )));
+            }
+        }
+
+        #[test]
+        fn offset_addressing() {
+            let interpreter = optimize(b">+>++<<-", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::IncrementAt(1, 1),
+                    Instruction::IncrementAt(2, 2),
+                    Instruction::Decrement(1),
+                ],
+            );
+
+            let interpreter = optimize(b",>.<<->>>+[<<.]", ArithmeticPolicy::Trap);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::Read,
+                    Instruction::WriteAt(1),
+                    Instruction::DecrementAt(-1, 1),
+                    Instruction::IncrementAt(2, 1),
+                    Instruction::MoveRight(2),
+                    Instruction::StartLoop(8),
+                    Instruction::WriteAt(-2),
+                    Instruction::MoveLeft(2),
+                    Instruction::EndLoop(5),
+                ],
+            );
+
+            let interpreter = optimize(b"<<,>>>", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[Instruction::ReadAt(-2), Instruction::MoveRight(1)],
+            );
+        }
+
+        #[test]
+        fn dumps_are_not_moved() {
+            let mut interpreter = Interpreter::with_configuration(Configuration {
+                optimize: true,
+                extensions: true,
+                ..Configuration::default()
+            });
+            interpreter.load(b">+>#<[-#]").unwrap();
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::IncrementAt(1, 1),
+                    Instruction::MoveRight(2),
+                    Instruction::Dump,
+                    Instruction::MoveLeft(1),
+                    Instruction::StartLoop(7),
+                    Instruction::Decrement(1),
+                    Instruction::Dump,
+                    Instruction::EndLoop(4),
+                ],
+            );
+        }
+
+        #[test]
+        fn spans() {
+            let interpreter = optimize(b"+\n>>[-]+<[<]\n,[->+<]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::Increment(1),
+                    Instruction::MoveRight(2),
+                    Instruction::SetZero,
+                    Instruction::Increment(1),
+                    Instruction::MoveLeft(1),
+                    Instruction::ScanLeft(1),
+                    Instruction::Read,
+                    Instruction::MultiplyAdd(vec![(1, 1)]),
+                ],
+            );
+            assert_eq!(
+                interpreter.spans(),
+                &[
+                    span((1, 1), (1, 1)),
+                    span((2, 1), (2, 2)),
+                    span((2, 3), (2, 5)),
+                    span((2, 6), (2, 6)),
+                    span((2, 7), (2, 7)),
+                    span((2, 8), (2, 10)),
+                    span((3, 1), (3, 1)),
+                    span((3, 2), (3, 7)),
+                ],
+            );
+
+            let interpreter = optimize(b">+\n>-<", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::IncrementAt(1, 1),
+                    Instruction::DecrementAt(2, 1),
+                    Instruction::MoveRight(1),
+                ],
+            );
+            assert_eq!(
+                interpreter.spans(),
+                &[
+                    span((1, 2), (1, 2)),
+                    span((2, 2), (2, 2)),
+                    span((1, 1), (2, 3))
+                ],
+            );
+        }
+
+        #[test]
+        fn loops_are_relinked() {
+            let interpreter = optimize(b"+[>[-]<[->+<]-]", ArithmeticPolicy::Wrap);
+            assert_eq!(
+                interpreter.program(),
+                &[
+                    Instruction::Increment(1),
+                    Instruction::StartLoop(7),
+                    Instruction::MoveRight(1),
+                    Instruction::SetZero,
+                    Instruction::MoveLeft(1),
+                    Instruction::MultiplyAdd(vec![(1, 1)]),
+                    Instruction::Decrement(1),
+                    Instruction::EndLoop(1),
+                ],
+            );
+        }
+
+        #[test]
+        fn optimization_is_disabled_by_default() {
+            let mut interpreter = Interpreter::new();
+            interpreter.load(b"[-][>][->+<]").unwrap();
+            assert_eq!(interpreter.program().len(), 12);
+        }
+    }
+}
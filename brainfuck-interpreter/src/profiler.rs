@@ -0,0 +1,401 @@
+use crate::{
+    error::RuntimeError,
+    instruction::Instruction,
+    interpreter::Interpreter,
+    machine::Machine,
+    span::Location,
+    tape::Tape,
+};
+use std::{
+    fmt::Write as _,
+    io::{
+        Read,
+        Write,
+    },
+};
+
+// Only the hottest loops and instructions are listed in the report.
+const HOT_SPOTS: usize = 10;
+
+// Longer loops are shortened in the report, so its columns stay aligned.
+const SNIPPET_LENGTH: usize = 32;
+
+const COMMANDS: &[u8] = b"+-<>[].,#";
+
+impl Interpreter {
+    pub fn profile(&self) -> Result<(Tape, Profile<'_>), RuntimeError> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        self.profile_with(stdin.lock(), stdout.lock())
+    }
+
+    pub fn profile_with<R: Read, W: Write>(
+        &self,
+        input: R,
+        mut output: W,
+    ) -> Result<(Tape, Profile<'_>), RuntimeError> {
+        self.with_input(input, |mut input| {
+            let mut machine = Machine::new(self);
+            let mut counts = vec![0; self.program().len()];
+            let mut max_head = 0;
+            while !machine.is_finished() {
+                machine.check_limits()?;
+                counts[machine.instruction()] += 1;
+                machine.step(&mut input, &mut output)?;
+                max_head = max_head.max(machine.head());
+            }
+            output.flush()?;
+
+            let profile = Profile {
+                interpreter: self,
+                counts,
+                steps: machine.steps(),
+                max_head,
+            };
+            Ok((machine.into_tape(), profile))
+        })
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Profile<'a> {
+    interpreter: &'a Interpreter,
+    counts: Vec<u64>,
+    steps: u64,
+    max_head: usize,
+}
+
+impl Profile<'_> {
+    #[inline]
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    #[inline]
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    #[inline]
+    pub fn max_head(&self) -> usize {
+        self.max_head
+    }
+
+    // The cost of a loop is the number of instructions executed between its
+    // brackets (including the brackets and the nested loops), and each
+    // execution of its ']' is an iteration.
+    pub fn loops(&self) -> Vec<LoopProfile> {
+        let mut loops = self
+            .interpreter
+            .program()
+            .iter()
+            .enumerate()
+            .filter_map(|(start, instruction)| {
+                match *instruction {
+                    Instruction::StartLoop(end) if self.counts[start] > 0 => {
+                        Some(LoopProfile {
+                            start,
+                            end,
+                            entries: self.counts[start],
+                            iterations: self.counts[end],
+                            cost: self.counts[start..=end].iter().sum(),
+                        })
+                    },
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        loops.sort_by(|a, b| b.cost.cmp(&a.cost).then(a.start.cmp(&b.start)));
+        loops
+    }
+
+    fn instructions(&self) -> Vec<usize> {
+        let mut instructions = (0..self.counts.len())
+            .filter(|&index| self.counts[index] > 0)
+            .collect::<Vec<_>>();
+        instructions.sort_by(|&a, &b| self.counts[b].cmp(&self.counts[a]).then(a.cmp(&b)));
+        instructions
+    }
+
+    fn share(&self, count: u64) -> f64 {
+        if self.steps == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.steps as f64
+        }
+    }
+
+    // The snippets are sliced out of the script by the spans of the instructions
+    // instead of being printed from the instructions, which may be optimized
+    // into code the script doesn't contain, and only the commands are kept, so
+    // each of them fits on a single line of the report.
+    fn snippet(&self, script: &[u8], start: usize, end: usize) -> String {
+        let spans = self.interpreter.spans();
+        let source = script
+            .get(offset(script, spans[start].start)..=offset(script, spans[end].end))
+            .unwrap_or_default();
+        let mut snippet = source
+            .iter()
+            .filter(|token| COMMANDS.contains(token))
+            .map(|&token| token as char)
+            .collect::<String>();
+        if snippet.len() > SNIPPET_LENGTH {
+            snippet.truncate(SNIPPET_LENGTH - 3);
+            snippet.push_str("...");
+        }
+        snippet
+    }
+}
+
+impl Profile<'_> {
+    // Renders a report of the hottest loops and instructions along with their
+    // locations and their code in the given script, which is the one the
+    // program is loaded from.
+    pub fn render(&self, script: &[u8]) -> String {
+        let spans = self.interpreter.spans();
+        let mut rendered = String::new();
+        writeln!(rendered, "instructions executed: {}", self.steps).unwrap();
+        writeln!(rendered, "maximum head reached: {}", self.max_head).unwrap();
+
+        let loops = self.loops();
+        if !loops.is_empty() {
+            writeln!(rendered).unwrap();
+            writeln!(rendered, "hottest loops:").unwrap();
+            writeln!(
+                rendered,
+                "{:>14} {:>7} {:>12}  {:<9} loop",
+                "cost", "share", "iterations", "at"
+            )
+            .unwrap();
+            for profile in loops.iter().take(HOT_SPOTS) {
+                writeln!(
+                    rendered,
+                    "{:>14} {:>6.2}% {:>12}  {:<9} {}",
+                    profile.cost,
+                    self.share(profile.cost),
+                    profile.iterations,
+                    spans[profile.start].start.to_string(),
+                    self.snippet(script, profile.start, profile.end),
+                )
+                .unwrap();
+            }
+        }
+
+        let instructions = self.instructions();
+        if !instructions.is_empty() {
+            writeln!(rendered).unwrap();
+            writeln!(rendered, "hottest instructions:").unwrap();
+            writeln!(
+                rendered,
+                "{:>14} {:>7}  {:<9} instruction",
+                "count", "share", "at"
+            )
+            .unwrap();
+            for &index in instructions.iter().take(HOT_SPOTS) {
+                writeln!(
+                    rendered,
+                    "{:>14} {:>6.2}%  {:<9} {}",
+                    self.counts[index],
+                    self.share(self.counts[index]),
+                    spans[index].start.to_string(),
+                    self.snippet(script, index, index),
+                )
+                .unwrap();
+            }
+        }
+
+        rendered
+    }
+}
+
+// The offset of the character at the given location in the script.
+fn offset(script: &[u8], location: Location) -> usize {
+    let line = script
+        .split_inclusive(|&token| token == b'\n')
+        .take(location.line - 1)
+        .map(<[u8]>::len)
+        .sum::<usize>();
+    line + location.column - 1
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct LoopProfile {
+    pub start: usize,
+    pub end: usize,
+    pub entries: u64,
+    pub iterations: u64,
+    pub cost: u64,
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod profile_with {
+            use crate::{
+                configuration::{
+                    Configuration,
+                    TapeGrowth,
+                },
+                error::RuntimeError,
+                interpreter::Interpreter,
+                profiler::LoopProfile,
+            };
+
+            fn load(configuration: Configuration, script: &[u8]) -> Interpreter {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                interpreter
+            }
+
+            #[test]
+            fn counts() {
+                let interpreter = load(Configuration::default(), b"++[>+++[>+<-]<-]>>.");
+                let mut output = Vec::new();
+                let (tape, profile) = interpreter.profile_with(&b""[..], &mut output).unwrap();
+                assert_eq!(output, [6]);
+                assert_eq!(&tape.cells()[..3], &[0, 0, 6]);
+
+                assert_eq!(
+                    profile.counts(),
+                    &[1, 1, 2, 2, 2, 6, 6, 6, 6, 6, 2, 2, 2, 1, 1]
+                );
+                assert_eq!(profile.steps(), 46);
+                assert_eq!(profile.max_head(), 2);
+                assert_eq!(
+                    profile.loops(),
+                    vec![
+                        LoopProfile {
+                            start: 1,
+                            end: 12,
+                            entries: 1,
+                            iterations: 2,
+                            cost: 43,
+                        },
+                        LoopProfile {
+                            start: 4,
+                            end: 9,
+                            entries: 2,
+                            iterations: 6,
+                            cost: 32,
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn skipped_loops() {
+                let interpreter = load(Configuration::default(), b"[+]+[-]");
+                let (_, profile) = interpreter.profile_with(&b""[..], Vec::new()).unwrap();
+                assert_eq!(profile.counts(), &[1, 0, 0, 1, 1, 1, 1]);
+                assert_eq!(
+                    profile.loops(),
+                    vec![
+                        LoopProfile {
+                            start: 4,
+                            end: 6,
+                            entries: 1,
+                            iterations: 1,
+                            cost: 3,
+                        },
+                        LoopProfile {
+                            start: 0,
+                            end: 2,
+                            entries: 1,
+                            iterations: 0,
+                            cost: 1,
+                        },
+                    ]
+                );
+            }
+
+            #[test]
+            fn runtime_error() {
+                let configuration = Configuration {
+                    tape_growth: TapeGrowth::Fixed,
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration, b"+<");
+                match interpreter.profile_with(&b""[..], Vec::new()) {
+                    Err(error) => {
+                        match error.without_location() {
+                            RuntimeError::CellUnderflow => {},
+                            _ => unreachable!(),
+                        }
+                    },
+                    Ok(_) => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod profile {
+    mod methods {
+        mod render {
+            use crate::{
+                configuration::Configuration,
+                interpreter::Interpreter,
+            };
+
+            #[test]
+            fn report() {
+                let script = b"++\n[>+++[>+<-]<-]>>.";
+                let mut interpreter = Interpreter::with_configuration(Configuration::default());
+                interpreter.load(script).unwrap();
+                let (_, profile) = interpreter.profile_with(&b""[..], Vec::new()).unwrap();
+                assert_eq!(
+                    profile.render(script),
+                    "\
+instructions executed: 46
+maximum head reached: 2
+
+hottest loops:
+          cost   share   iterations  at        loop
+            43  93.48%            2  2:1       [>+++[>+<-]<-]
+            32  69.57%            6  2:6       [>+<-]
+
+hottest instructions:
+         count   share  at        instruction
+             6  13.04%  2:7       >
+             6  13.04%  2:8       +
+             6  13.04%  2:9       <
+             6  13.04%  2:10      -
+             6  13.04%  2:11      ]
+             2   4.35%  2:2       >
+             2   4.35%  2:3       +++
+             2   4.35%  2:6       [
+             2   4.35%  2:12      <
+             2   4.35%  2:13      -
+",
+                );
+            }
+
+            #[test]
+            fn snippets() {
+                let script = b"+[>+>+>+>+>+>+>+>+>+>+>+>+>+>+>+>+<<<<<<<<<<<<<<<<-]";
+                let mut interpreter = Interpreter::with_configuration(Configuration::default());
+                interpreter.load(script).unwrap();
+                let (_, profile) = interpreter.profile_with(&b""[..], Vec::new()).unwrap();
+                assert!(
+                    profile
+                        .render(script)
+                        .contains(" [>+>+>+>+>+>+>+>+>+>+>+>+>+>+...\n")
+                );
+
+                let script = b"++ outer [>+++\n[>++++<-] inner\n<-]";
+                let mut interpreter = Interpreter::with_configuration(Configuration {
+                    optimize: true,
+                    ..Configuration::default()
+                });
+                interpreter.load(script).unwrap();
+                let (_, profile) = interpreter.profile_with(&b""[..], Vec::new()).unwrap();
+                let report = profile.render(script);
+                assert!(report.contains("  1:10      [>+++[>++++<-]<-]\n"));
+                assert!(report.contains("  2:1       [>++++<-]\n"));
+            }
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    error::{
+        RuntimeError,
+        SyntaxError,
+    },
+    interpreter::Interpreter,
+    machine::Machine,
+    tape::Tape,
+};
+use std::io::{
+    BufRead,
+    Read,
+    Write,
+};
+
+const PROMPT: &str = "bf> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+impl Interpreter {
+    pub fn repl(&self) -> Result<Tape, RuntimeError> {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        self.repl_with(stdin.lock(), stdout.lock())
+    }
+
+    // Each entry is loaded into a new interpreter with the same configuration, and
+    // executed on the tape and the head left by the previous entries. Entries
+    // with unclosed loops are continued on the next lines, and errors are
+    // reported without losing the tape.
+    pub fn repl_with<R: BufRead, W: Write>(
+        &self,
+        mut input: R,
+        mut output: W,
+    ) -> Result<Tape, RuntimeError> {
+        let mut tape = Tape::new(self.configuration());
+        let mut head = 0;
+        let mut script = Vec::new();
+        loop {
+            write!(
+                output,
+                "{}",
+                if script.is_empty() {
+                    PROMPT
+                } else {
+                    CONTINUATION_PROMPT
+                }
+            )?;
+            output.flush()?;
+
+            if input.read_until(b'\n', &mut script)? == 0 {
+                writeln!(output)?;
+                return Ok(tape);
+            }
+
+            let mut entry = Interpreter::with_configuration(self.configuration().clone());
+            if let Err(errors) = entry.load_collecting_errors(&script) {
+                let unclosed = errors
+                    .iter()
+                    .all(|error| matches!(error, SyntaxError::MissingClosingBracket(_, _)));
+                if unclosed {
+                    continue;
+                }
+                for error in errors {
+                    writeln!(output, "{}", error)?;
+                }
+                script.clear();
+                continue;
+            }
+            script.clear();
+
+            let mut machine = Machine::with_tape(&entry, tape, head);
+            let result = execute(&entry, &mut machine, &mut input, &mut output);
+            head = machine.head();
+            tape = machine.into_tape();
+
+            output.flush()?;
+            if let Err(error) = result {
+                writeln!(output, "{}", error)?;
+            }
+            tape.dump(head, &mut output)?;
+        }
+    }
+}
+
+fn execute<R: BufRead, W: Write>(
+    entry: &Interpreter,
+    machine: &mut Machine,
+    input: &mut R,
+    output: &mut W,
+) -> Result<(), RuntimeError> {
+    while !machine.is_finished() {
+        machine.check_limits()?;
+        if entry.embedded_input().is_some() {
+            machine.step_buffered(output)?;
+        } else {
+            machine.step(&mut input.bytes(), output)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod repl_with {
+            use crate::{
+                configuration::{
+                    Configuration,
+                    TapeGrowth,
+                },
+                interpreter::Interpreter,
+                tape::Tape,
+            };
+
+            fn repl(configuration: Configuration, session: &[u8]) -> (String, Tape) {
+                let interpreter = Interpreter::with_configuration(configuration);
+                let mut output = Vec::new();
+                let tape = interpreter.repl_with(session, &mut output).unwrap();
+                (String::from_utf8(output).unwrap(), tape)
+            }
+
+            #[test]
+            fn persistent_tape() {
+                let (output, tape) = repl(Configuration::default(), b"+++>\n++\n\n<.\n");
+                assert_eq!(
+                    output,
+                    "\
+bf> head = 1, cells 0..=9 = 3 [0] 0 0 0 0 0 0 0 0
+bf> head = 1, cells 0..=9 = 3 [2] 0 0 0 0 0 0 0 0
+bf> head = 1, cells 0..=9 = 3 [2] 0 0 0 0 0 0 0 0
+bf> \u{3}head = 0, cells 0..=8 = [3] 2 0 0 0 0 0 0 0
+bf> \n",
+                );
+                assert_eq!(&tape.cells()[..3], &[3, 2, 0]);
+            }
+
+            #[test]
+            fn multi_line() {
+                let (output, tape) = repl(Configuration::default(), b"++++[>\n++[>+<-]\n<-]\n");
+                assert_eq!(
+                    output,
+                    "\
+bf> ... ... head = 0, cells 0..=8 = [0] 0 8 0 0 0 0 0 0
+bf> \n",
+                );
+                assert_eq!(&tape.cells()[..3], &[0, 0, 8]);
+            }
+
+            #[test]
+            fn syntax_error() {
+                let (output, tape) = repl(Configuration::default(), b"+\n]\n[]]\n+\n");
+                assert_eq!(
+                    output
+                        .matches("unable to find the opening bracket of ']'")
+                        .count(),
+                    2
+                );
+                assert!(output.contains("at 1:1"));
+                assert!(output.contains("at 1:3"));
+                assert!(output.ends_with("head = 0, cells 0..=8 = [2] 0 0 0 0 0 0 0 0\nbf> \n"));
+                assert_eq!(tape[0], 2);
+            }
+
+            #[test]
+            fn runtime_error() {
+                let configuration = Configuration {
+                    tape_growth: TapeGrowth::Fixed,
+                    ..Configuration::default()
+                };
+                let (output, tape) = repl(configuration, b"+>++<<\n+\n");
+                assert!(output.contains("attempted to access a negative cell (at 1:5 in '<<')"));
+                assert!(output.ends_with("head = 1, cells 0..=9 = 1 [3] 0 0 0 0 0 0 0 0\nbf> \n"));
+                assert_eq!(&tape.cells()[..2], &[1, 3]);
+            }
+
+            #[test]
+            fn input() {
+                let (output, _) = repl(Configuration::default(), b",.,.\nab\n");
+                assert_eq!(
+                    output,
+                    "\
+bf> abhead = 0, cells 0..=8 = [98] 0 0 0 0 0 0 0 0
+bf> head = 0, cells 0..=8 = [98] 0 0 0 0 0 0 0 0
+bf> \n",
+                );
+            }
+        }
+    }
+}
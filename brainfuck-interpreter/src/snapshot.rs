@@ -0,0 +1,419 @@
+use crate::{
+    bytecode::{
+        self,
+        Reader,
+    },
+    configuration::{
+        Configuration,
+        EndOfInputPolicy,
+        TapeGrowth,
+    },
+    error::{
+        BytecodeError,
+        SnapshotError,
+    },
+    interpreter::Interpreter,
+    machine::Machine,
+    tape::Tape,
+};
+use std::convert::TryFrom;
+
+const MAGIC: &[u8; 4] = b"BFSS";
+const VERSION: u16 = 2;
+const HEADER_SIZE: usize = 26;
+
+// A snapshot is pinned to the configuration the execution depends on and to
+// the fingerprint of the program, both of which are in its header along with
+// the checksum of the state of the machine it holds. The state is the current
+// instruction, the head, the number of steps (as a little endian u64), whether
+// the end of the input is reached, the cells, the rightmost cell reached by the
+// head and the pending input and output. See the bytecode for the common
+// layout of the formats.
+impl Machine<'_> {
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let cells = self.tape.cells();
+        let mut payload = Vec::with_capacity(cells.len() + self.input.len() + self.output.len());
+        bytecode::write_unsigned(&mut payload, self.instruction);
+        bytecode::write_unsigned(&mut payload, self.head);
+        payload.extend_from_slice(&self.steps.to_le_bytes());
+        payload.push(self.end_of_input as u8);
+        bytecode::write_unsigned(&mut payload, cells.len());
+        for &cell in cells {
+            bytecode::write_unsigned(&mut payload, cell as usize);
+        }
+        bytecode::write_unsigned(&mut payload, self.tape.reached());
+        bytecode::write_unsigned(&mut payload, self.input.len());
+        payload.extend(&self.input);
+        bytecode::write_unsigned(&mut payload, self.output.len());
+        payload.extend_from_slice(&self.output);
+
+        let mut snapshot = Vec::with_capacity(HEADER_SIZE + payload.len());
+        snapshot.extend_from_slice(MAGIC);
+        snapshot.extend_from_slice(&VERSION.to_le_bytes());
+        snapshot.extend_from_slice(&flags(self.interpreter.configuration()));
+        snapshot.extend_from_slice(&fingerprint(self.interpreter).to_le_bytes());
+        snapshot.extend_from_slice(&bytecode::hash(&payload).to_le_bytes());
+        snapshot.extend_from_slice(&payload);
+        snapshot
+    }
+}
+
+impl<'a> Machine<'a> {
+    pub fn load_snapshot(
+        interpreter: &'a Interpreter,
+        snapshot: &[u8],
+    ) -> Result<Machine<'a>, SnapshotError> {
+        if snapshot.len() < MAGIC.len() || &snapshot[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::InvalidMagic);
+        }
+        if snapshot.len() < HEADER_SIZE {
+            return Err(SnapshotError::Malformed);
+        }
+
+        let version = u16::from_le_bytes([snapshot[4], snapshot[5]]);
+        if version != VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let mut program_fingerprint = [0; 8];
+        program_fingerprint.copy_from_slice(&snapshot[10..18]);
+        let mut checksum = [0; 8];
+        checksum.copy_from_slice(&snapshot[18..26]);
+
+        let payload = &snapshot[HEADER_SIZE..];
+        if u64::from_le_bytes(checksum) != bytecode::hash(payload) {
+            return Err(SnapshotError::ChecksumMismatch);
+        }
+        if u64::from_le_bytes(program_fingerprint) != fingerprint(interpreter) {
+            return Err(SnapshotError::ProgramMismatch);
+        }
+        if snapshot[6..10] != flags(interpreter.configuration()) {
+            return Err(SnapshotError::ConfigurationMismatch);
+        }
+
+        let mut reader = Reader {
+            payload,
+            position: 0,
+        };
+        let machine = restore(interpreter, &mut reader).map_err(|_| SnapshotError::Malformed)?;
+
+        // A fixed tape never changes its size, so it must be as large as the configured
+        // one.
+        let configuration = interpreter.configuration();
+        if configuration.tape_growth == TapeGrowth::Fixed &&
+            machine.tape.len() != configuration.tape_size.max(1)
+        {
+            return Err(SnapshotError::ConfigurationMismatch);
+        }
+
+        Ok(machine)
+    }
+}
+
+fn restore<'a>(
+    interpreter: &'a Interpreter,
+    reader: &mut Reader,
+) -> Result<Machine<'a>, BytecodeError> {
+    let configuration = interpreter.configuration();
+
+    let instruction = reader.unsigned()?;
+    let head = reader.unsigned()?;
+    let mut steps = [0; 8];
+    steps.copy_from_slice(reader.bytes(8)?);
+    let end_of_input = match reader.byte()? {
+        0 => false,
+        1 => true,
+        _ => return Err(BytecodeError::Malformed),
+    };
+
+    let length = reader.unsigned()?;
+    let mut cells = Vec::with_capacity(length.min(reader.payload.len()));
+    for _ in 0..length {
+        match u32::try_from(reader.unsigned()?) {
+            Ok(cell) if cell <= configuration.cell_width.max() => cells.push(cell),
+            _ => return Err(BytecodeError::Malformed),
+        }
+    }
+    let reached = reader.unsigned()?;
+
+    let length = reader.unsigned()?;
+    let input = reader.bytes(length)?.iter().copied().collect();
+    let length = reader.unsigned()?;
+    let output = reader.bytes(length)?.to_vec();
+
+    if reader.position != reader.payload.len() ||
+        instruction > interpreter.program().len() ||
+        head >= cells.len() ||
+        reached >= cells.len()
+    {
+        return Err(BytecodeError::Malformed);
+    }
+
+    Ok(Machine {
+        interpreter,
+        tape: Tape::with_cells(configuration, cells, reached),
+        head,
+        instruction,
+        steps: u64::from_le_bytes(steps),
+        input,
+        end_of_input,
+        output,
+        deadline: interpreter.deadline(),
+    })
+}
+
+fn flags(configuration: &Configuration) -> [u8; 4] {
+    let tape_growth = match configuration.tape_growth {
+        TapeGrowth::Fixed => 0,
+        TapeGrowth::Right => 1,
+        TapeGrowth::Both => 2,
+    };
+    let end_of_input = match configuration.end_of_input {
+        EndOfInputPolicy::Zero => 0,
+        EndOfInputPolicy::Max => 1,
+        EndOfInputPolicy::Unchanged => 2,
+    };
+    [
+        configuration.cell_width.bits() as u8,
+        tape_growth,
+        bytecode::arithmetic(configuration),
+        end_of_input,
+    ]
+}
+
+fn fingerprint(interpreter: &Interpreter) -> u64 {
+    let mut encoded = Vec::with_capacity(interpreter.program().len() * 2);
+    for instruction in interpreter.program() {
+        bytecode::encode(&mut encoded, instruction);
+    }
+    bytecode::hash(&encoded)
+}
+
+#[cfg(test)]
+mod machine {
+    mod associated_functions {
+        mod load_snapshot {
+            use crate::{
+                bytecode::hash,
+                configuration::{
+                    ArithmeticPolicy,
+                    CellWidth,
+                    Configuration,
+                    EndOfInputPolicy,
+                    TapeGrowth,
+                },
+                error::SnapshotError,
+                interpreter::Interpreter,
+                machine::{
+                    Machine,
+                    Status,
+                },
+            };
+
+            fn load(configuration: Configuration, script: &[u8]) -> Interpreter {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                interpreter
+            }
+
+            // Recomputes the checksum, so the changes to the payload are not caught by it.
+            fn reseal(snapshot: &mut [u8]) {
+                let checksum = hash(&snapshot[26..]);
+                snapshot[18..26].copy_from_slice(&checksum.to_le_bytes());
+            }
+
+            #[test]
+            fn round_trip() {
+                let configuration = Configuration {
+                    cell_width: CellWidth::U16,
+                    tape_growth: TapeGrowth::Both,
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration, b"<+++[->++++<]>.,.>,.<<-.");
+                let mut machine = Machine::new(&interpreter);
+                machine.feed_input(b"a");
+                assert_eq!(machine.run(u64::MAX).unwrap(), Status::NeedsInput);
+
+                let snapshot = machine.save_snapshot();
+                let restored = Machine::load_snapshot(&interpreter, &snapshot).unwrap();
+                assert_eq!(restored.tape().cells(), machine.tape().cells());
+                assert_eq!(restored.tape().used_cells(), machine.tape().used_cells());
+                assert_eq!(restored.head(), machine.head());
+                assert_eq!(restored.instruction(), machine.instruction());
+                assert_eq!(restored.steps(), machine.steps());
+                assert_eq!(restored.save_snapshot(), snapshot);
+
+                for machine in &mut [machine, restored] {
+                    machine.feed_input(b"b");
+                    machine.end_input();
+                    assert_eq!(machine.run(u64::MAX).unwrap(), Status::Finished);
+                    assert_eq!(machine.take_output(), b"\x0cab\xff");
+                }
+            }
+
+            #[test]
+            fn pending_input() {
+                let interpreter = load(Configuration::default(), b"+,.,.,.");
+                let mut machine = Machine::new(&interpreter);
+                machine.feed_input(b"xyz");
+                machine.end_input();
+                assert_eq!(machine.run(3).unwrap(), Status::Paused);
+
+                let snapshot = machine.save_snapshot();
+                let mut restored = Machine::load_snapshot(&interpreter, &snapshot).unwrap();
+                assert_eq!(restored.run(u64::MAX).unwrap(), Status::Finished);
+                assert_eq!(restored.take_output(), b"xyz");
+            }
+
+            #[test]
+            fn invalid_magic() {
+                let interpreter = load(Configuration::default(), b"+");
+                for snapshot in &[&b""[..], b"BFS", b"BFBC\x01\x00"] {
+                    match Machine::load_snapshot(&interpreter, snapshot) {
+                        Err(SnapshotError::InvalidMagic) => {},
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            #[test]
+            fn unsupported_version() {
+                let interpreter = load(Configuration::default(), b"+");
+                let mut snapshot = Machine::new(&interpreter).save_snapshot();
+                snapshot[4..6].copy_from_slice(&3u16.to_le_bytes());
+                match Machine::load_snapshot(&interpreter, &snapshot) {
+                    Err(SnapshotError::UnsupportedVersion(3)) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn checksum_mismatch() {
+                let interpreter = load(Configuration::default(), b"+");
+                let mut snapshot = Machine::new(&interpreter).save_snapshot();
+                let last = snapshot.len() - 1;
+                snapshot[last] ^= 1;
+                match Machine::load_snapshot(&interpreter, &snapshot) {
+                    Err(SnapshotError::ChecksumMismatch) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn program_mismatch() {
+                let interpreter = load(Configuration::default(), b"+");
+                let snapshot = Machine::new(&interpreter).save_snapshot();
+                let other = load(Configuration::default(), b"-");
+                match Machine::load_snapshot(&other, &snapshot) {
+                    Err(SnapshotError::ProgramMismatch) => {},
+                    _ => unreachable!(),
+                }
+            }
+
+            #[test]
+            fn configuration_mismatch() {
+                let interpreter = load(Configuration::default(), b"+");
+                let snapshot = Machine::new(&interpreter).save_snapshot();
+                for configuration in &[
+                    Configuration {
+                        cell_width: CellWidth::U32,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        tape_growth: TapeGrowth::Right,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        arithmetic: ArithmeticPolicy::Saturate,
+                        ..Configuration::default()
+                    },
+                    Configuration {
+                        end_of_input: EndOfInputPolicy::Max,
+                        ..Configuration::default()
+                    },
+                ] {
+                    let other = load(configuration.clone(), b"+");
+                    match Machine::load_snapshot(&other, &snapshot) {
+                        Err(SnapshotError::ConfigurationMismatch) => {},
+                        _ => unreachable!(),
+                    }
+                }
+            }
+
+            #[test]
+            fn tape_size_mismatch() {
+                let configuration = Configuration {
+                    tape_size: 10,
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration.clone(), b"+");
+                let snapshot = Machine::new(&interpreter).save_snapshot();
+
+                let other = load(Configuration::default(), b"+");
+                match Machine::load_snapshot(&other, &snapshot) {
+                    Err(SnapshotError::ConfigurationMismatch) => {},
+                    _ => unreachable!(),
+                }
+
+                // A growing tape only starts with the configured size.
+                let growing = Configuration {
+                    tape_growth: TapeGrowth::Right,
+                    ..configuration
+                };
+                let interpreter = load(growing.clone(), b"+");
+                let snapshot = Machine::new(&interpreter).save_snapshot();
+                let other = load(
+                    Configuration {
+                        tape_size: 20,
+                        ..growing
+                    },
+                    b"+",
+                );
+                assert!(Machine::load_snapshot(&other, &snapshot).is_ok());
+            }
+
+            #[test]
+            fn malformed() {
+                let configuration = Configuration {
+                    tape_size: 1,
+                    ..Configuration::default()
+                };
+                let interpreter = load(configuration, b"+");
+                let snapshot = Machine::new(&interpreter).save_snapshot();
+
+                match Machine::load_snapshot(&interpreter, &snapshot[..20]) {
+                    Err(SnapshotError::Malformed) => {},
+                    _ => unreachable!(),
+                }
+
+                // Truncated payload, a head outside of the tape, an instruction outside of the
+                // program, an invalid end of input flag, a cell above the maximum value and a
+                // reached cell outside of the tape.
+                let payloads: &[&[u8]] = &[
+                    &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
+                    &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0],
+                    &[2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0],
+                    &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 0, 0, 0, 0],
+                    &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x80, 0x02, 0, 0, 0],
+                    &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 0],
+                ];
+                for payload in payloads {
+                    let mut corrupted = snapshot[..26].to_vec();
+                    corrupted.extend_from_slice(payload);
+                    reseal(&mut corrupted);
+                    match Machine::load_snapshot(&interpreter, &corrupted) {
+                        Err(SnapshotError::Malformed) => {},
+                        _ => unreachable!(),
+                    }
+                }
+
+                let mut valid = snapshot[..26].to_vec();
+                valid.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0xff, 0x01, 0, 0, 0]);
+                reseal(&mut valid);
+                let machine = Machine::load_snapshot(&interpreter, &valid).unwrap();
+                assert!(machine.is_finished());
+                assert_eq!(machine.tape().cells(), &[255]);
+            }
+        }
+    }
+}
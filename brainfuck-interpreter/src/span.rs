@@ -0,0 +1,113 @@
+use std::fmt::{
+    self,
+    Display,
+};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    #[inline]
+    pub fn new(line: usize, column: usize) -> Location {
+        Location { line, column }
+    }
+}
+
+impl Display for Location {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod location {
+    mod traits {
+        mod display {
+            use crate::span::Location;
+
+            #[test]
+            fn display() {
+                assert_eq!(format!("{}", Location::new(3, 14)), "3:14");
+            }
+        }
+
+        mod ord {
+            use crate::span::Location;
+
+            #[test]
+            fn ord() {
+                assert!(Location::new(1, 9) < Location::new(2, 1));
+                assert!(Location::new(2, 1) < Location::new(2, 3));
+                assert_eq!(Location::new(2, 3), Location::new(2, 3));
+            }
+        }
+    }
+}
+
+
+// The start and the end of a span are the locations of the first and the last
+// characters of the script, which the instruction is made out of (e.g., "+++"
+// on the first line spans 1:1 to 1:3).
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Span {
+    pub start: Location,
+    pub end: Location,
+}
+
+impl Span {
+    #[inline]
+    pub fn new(start: Location, end: Location) -> Span {
+        Span { start, end }
+    }
+
+    #[inline]
+    pub fn at(location: Location) -> Span {
+        Span::new(location, location)
+    }
+
+    #[inline]
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+#[cfg(test)]
+mod span {
+    mod associated_functions {
+        mod at {
+            use crate::span::{
+                Location,
+                Span,
+            };
+
+            #[test]
+            fn at() {
+                let location = Location::new(1, 2);
+                assert_eq!(Span::at(location), Span::new(location, location));
+            }
+        }
+    }
+
+    mod methods {
+        mod to {
+            use crate::span::{
+                Location,
+                Span,
+            };
+
+            #[test]
+            fn to() {
+                let first = Span::new(Location::new(1, 1), Location::new(1, 3));
+                let second = Span::at(Location::new(2, 5));
+                assert_eq!(
+                    first.to(second),
+                    Span::new(Location::new(1, 1), Location::new(2, 5))
+                );
+            }
+        }
+    }
+}
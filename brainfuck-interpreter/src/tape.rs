@@ -0,0 +1,634 @@
+use crate::{
+    configuration::{
+        ArithmeticPolicy,
+        CellWidth,
+        Configuration,
+        TapeGrowth,
+    },
+    error::RuntimeError,
+};
+use std::{
+    fmt::Write as _,
+    io::{
+        self,
+        Write,
+    },
+    ops::Index,
+};
+
+pub(crate) const DUMP_RADIUS: usize = 8;
+const HEX_CELLS_PER_LINE: usize = 16;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Tape {
+    cells: Vec<u32>,
+    start: usize,
+    // The index of the rightmost cell the head has reached, relative to the first cell.
+    reached: usize,
+    cell_width: CellWidth,
+    growth: TapeGrowth,
+    arithmetic: ArithmeticPolicy,
+}
+
+impl Tape {
+    pub fn new(configuration: &Configuration) -> Tape {
+        Tape {
+            cells: vec![0; configuration.tape_size.max(1)],
+            start: 0,
+            reached: 0,
+            cell_width: configuration.cell_width,
+            growth: configuration.tape_growth,
+            arithmetic: configuration.arithmetic,
+        }
+    }
+
+    pub(crate) fn with_cells(
+        configuration: &Configuration,
+        cells: Vec<u32>,
+        reached: usize,
+    ) -> Tape {
+        Tape {
+            cells,
+            start: 0,
+            reached,
+            cell_width: configuration.cell_width,
+            growth: configuration.tape_growth,
+            arithmetic: configuration.arithmetic,
+        }
+    }
+}
+
+impl Tape {
+    #[inline]
+    pub fn cells(&self) -> &[u32] {
+        &self.cells[self.start..]
+    }
+
+    #[inline]
+    pub fn cell_width(&self) -> CellWidth {
+        self.cell_width
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.cells.len() - self.start
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<u32> {
+        self.cells().get(index).cloned()
+    }
+
+    #[inline]
+    pub(crate) fn reached(&self) -> usize {
+        self.reached
+    }
+
+    #[inline]
+    pub fn into_cells(mut self) -> Vec<u32> {
+        self.cells.drain(..self.start);
+        self.cells
+    }
+}
+
+impl Tape {
+    #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+    #[inline]
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut u32 {
+        self.cells[self.start..].as_mut_ptr()
+    }
+
+    #[cfg(all(feature = "jit", target_arch = "x86_64"))]
+    #[inline]
+    pub(crate) fn reach(&mut self, index: usize) {
+        self.reached = self.reached.max(index);
+    }
+
+    pub(crate) fn move_left(&mut self, head: usize, amount: usize) -> Result<usize, RuntimeError> {
+        if amount <= head {
+            return Ok(head - amount);
+        }
+        if !self.growth.grows_left() {
+            return Err(RuntimeError::CellUnderflow);
+        }
+
+        let missing = amount - head;
+        if missing > self.start {
+            let slack = missing.max(self.len());
+            let mut cells = vec![0; slack + self.len()];
+            cells[slack..].copy_from_slice(self.cells());
+            self.cells = cells;
+            self.start = slack;
+        }
+        self.start -= missing;
+        self.reached += missing;
+
+        Ok(0)
+    }
+
+    pub(crate) fn move_right(&mut self, head: usize, amount: usize) -> Result<usize, RuntimeError> {
+        let new_head = if amount < self.len() - head {
+            head + amount
+        } else {
+            if !self.growth.grows_right() {
+                return Err(RuntimeError::CellOverflow);
+            }
+            let new_head = head.checked_add(amount).ok_or(RuntimeError::CellOverflow)?;
+            self.cells.resize(self.start + new_head + 1, 0);
+            new_head
+        };
+
+        self.reached = self.reached.max(new_head);
+        Ok(new_head)
+    }
+
+    pub(crate) fn locate(
+        &mut self,
+        head: &mut usize,
+        offset: isize,
+    ) -> Result<usize, RuntimeError> {
+        if offset < 0 {
+            let distance = offset.unsigned_abs();
+            let location = self.move_left(*head, distance)?;
+            *head = location + distance;
+            Ok(location)
+        } else {
+            self.move_right(*head, offset as usize)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn increment(&mut self, head: usize, amount: usize) -> bool {
+        let max = u64::from(self.cell_width.max());
+        let cell = &mut self.cells[self.start + head];
+        match self.arithmetic {
+            ArithmeticPolicy::Wrap => {
+                *cell = (u64::from(*cell).wrapping_add(amount as u64) & max) as u32;
+            },
+            ArithmeticPolicy::Saturate => {
+                *cell = u64::from(*cell).saturating_add(amount as u64).min(max) as u32;
+            },
+            ArithmeticPolicy::Trap => {
+                match u64::from(*cell).checked_add(amount as u64) {
+                    Some(sum) if sum <= max => *cell = sum as u32,
+                    _ => return false,
+                }
+            },
+        }
+        true
+    }
+
+    #[inline]
+    pub(crate) fn decrement(&mut self, head: usize, amount: usize) -> bool {
+        let max = u64::from(self.cell_width.max());
+        let cell = &mut self.cells[self.start + head];
+        match self.arithmetic {
+            ArithmeticPolicy::Wrap => {
+                *cell = (u64::from(*cell).wrapping_sub(amount as u64) & max) as u32;
+            },
+            ArithmeticPolicy::Saturate => {
+                *cell = u64::from(*cell).saturating_sub(amount as u64) as u32;
+            },
+            ArithmeticPolicy::Trap => {
+                match u64::from(*cell).checked_sub(amount as u64) {
+                    Some(difference) => *cell = difference as u32,
+                    None => return false,
+                }
+            },
+        }
+        true
+    }
+
+    #[inline]
+    pub(crate) fn multiply_add(&mut self, head: usize, value: u32, factor: isize) {
+        let max = u64::from(self.cell_width.max());
+        let cell = &mut self.cells[self.start + head];
+        let product = (factor as i64 as u64).wrapping_mul(u64::from(value));
+        *cell = (u64::from(*cell).wrapping_add(product) & max) as u32;
+    }
+
+    #[inline]
+    pub(crate) fn set(&mut self, head: usize, value: u32) {
+        self.cells[self.start + head] = value & self.cell_width.max();
+    }
+
+    pub(crate) fn dump<W: Write>(&self, head: usize, output: &mut W) -> io::Result<()> {
+        let start = head.saturating_sub(DUMP_RADIUS);
+        let end = head.saturating_add(DUMP_RADIUS).min(self.len() - 1);
+        write!(output, "head = {}, cells {}..={} =", head, start, end)?;
+        for cell in start..=end {
+            if cell == head {
+                write!(output, " [{}]", self[cell])?;
+            } else {
+                write!(output, " {}", self[cell])?;
+            }
+        }
+        writeln!(output)
+    }
+}
+
+impl Tape {
+    // Cells to the right of the rightmost cell the head has reached were never
+    // touched by the program, so they are left out of the exported tape.
+    pub fn used_cells(&self) -> &[u32] {
+        &self.cells()[..=self.reached]
+    }
+
+    // Same layout as `hexdump -C`, except that each cell takes as many digits as
+    // its width needs.
+    pub fn to_hex(&self) -> String {
+        let digits = self.cell_width.bits() as usize / 4;
+        let mut hex = String::new();
+        for (line, cells) in self.used_cells().chunks(HEX_CELLS_PER_LINE).enumerate() {
+            write!(hex, "{:08x}", line * HEX_CELLS_PER_LINE).unwrap();
+            for column in 0..HEX_CELLS_PER_LINE {
+                if column % 8 == 0 {
+                    hex.push(' ');
+                }
+                match cells.get(column) {
+                    Some(cell) => write!(hex, " {:0digits$x}", cell, digits = digits).unwrap(),
+                    None => hex.push_str(&" ".repeat(digits + 1)),
+                }
+            }
+            hex.push_str("  |");
+            for &cell in cells {
+                hex.push(
+                    if (0x20..0x7f).contains(&cell) {
+                        cell as u8 as char
+                    } else {
+                        '.'
+                    },
+                );
+            }
+            hex.push_str("|\n");
+        }
+        hex
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.encode(self.used_cells())
+    }
+
+    #[inline]
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.encode(self.cells())
+    }
+
+    // Each cell takes as many bytes as its width needs, in little endian byte
+    // order.
+    fn encode(&self, cells: &[u32]) -> Vec<u8> {
+        let width = self.cell_width.bits() as usize / 8;
+        let mut bytes = Vec::with_capacity(cells.len() * width);
+        for cell in cells {
+            bytes.extend_from_slice(&cell.to_le_bytes()[..width]);
+        }
+        bytes
+    }
+}
+
+impl Index<usize> for Tape {
+    type Output = u32;
+
+    #[inline]
+    fn index(&self, index: usize) -> &u32 {
+        &self.cells()[index]
+    }
+}
+
+#[cfg(test)]
+mod tape {
+    mod associated_functions {
+        mod new {
+            use crate::{
+                configuration::{
+                    CellWidth,
+                    Configuration,
+                },
+                tape::Tape,
+            };
+
+            #[test]
+            fn new() {
+                let configuration = Configuration {
+                    tape_size: 16,
+                    cell_width: CellWidth::U16,
+                    ..Configuration::default()
+                };
+                let tape = Tape::new(&configuration);
+                assert_eq!(tape.cells(), &[0; 16]);
+                assert_eq!(tape.cell_width(), CellWidth::U16);
+            }
+
+            #[test]
+            fn new_with_zero_size() {
+                let configuration = Configuration {
+                    tape_size: 0,
+                    ..Configuration::default()
+                };
+                let tape = Tape::new(&configuration);
+                assert_eq!(tape.len(), 1);
+            }
+        }
+    }
+
+    mod methods {
+        use crate::{
+            configuration::{
+                ArithmeticPolicy,
+                CellWidth,
+                Configuration,
+                TapeGrowth,
+            },
+            error::RuntimeError,
+            tape::Tape,
+        };
+
+        fn tape(cell_width: CellWidth, tape_growth: TapeGrowth) -> Tape {
+            Tape::new(&Configuration {
+                tape_size: 4,
+                cell_width,
+                tape_growth,
+                ..Configuration::default()
+            })
+        }
+
+        fn tape_with_arithmetic(arithmetic: ArithmeticPolicy) -> Tape {
+            Tape::new(&Configuration {
+                tape_size: 4,
+                arithmetic,
+                ..Configuration::default()
+            })
+        }
+
+        #[test]
+        fn move_left_on_fixed_tape() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            assert_eq!(tape.move_left(3, 3).unwrap(), 0);
+            match tape.move_left(3, 4) {
+                Err(RuntimeError::CellUnderflow) => {},
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn move_left_on_tape_growing_to_the_left() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Both);
+            tape.set(0, 42);
+            assert_eq!(tape.move_left(0, 3).unwrap(), 0);
+            assert_eq!(tape.cells(), &[0, 0, 0, 42, 0, 0, 0]);
+            assert_eq!(tape.move_left(0, 1).unwrap(), 0);
+            assert_eq!(tape.cells(), &[0, 0, 0, 0, 42, 0, 0, 0]);
+            assert_eq!(tape.into_cells(), vec![0, 0, 0, 0, 42, 0, 0, 0]);
+        }
+
+        #[test]
+        fn move_right_on_fixed_tape() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            assert_eq!(tape.move_right(0, 3).unwrap(), 3);
+            match tape.move_right(1, 3) {
+                Err(RuntimeError::CellOverflow) => {},
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn move_right_on_tape_growing_to_the_right() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Right);
+            assert_eq!(tape.move_right(1, 5).unwrap(), 6);
+            assert_eq!(tape.len(), 7);
+            match tape.move_left(0, 1) {
+                Err(RuntimeError::CellUnderflow) => {},
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn locate() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            let mut head = 1;
+            assert_eq!(tape.locate(&mut head, 2).unwrap(), 3);
+            assert_eq!(tape.locate(&mut head, -1).unwrap(), 0);
+            assert_eq!(head, 1);
+            match tape.locate(&mut head, 3) {
+                Err(RuntimeError::CellOverflow) => {},
+                _ => unreachable!(),
+            }
+            match tape.locate(&mut head, -2) {
+                Err(RuntimeError::CellUnderflow) => {},
+                _ => unreachable!(),
+            }
+        }
+
+        #[test]
+        fn locate_on_tape_growing_in_both_directions() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Both);
+            let mut head = 1;
+            tape.set(head, 42);
+            assert_eq!(tape.locate(&mut head, -3).unwrap(), 0);
+            assert_eq!(head, 3);
+            assert_eq!(tape[head], 42);
+            assert_eq!(tape.locate(&mut head, 4).unwrap(), 7);
+            assert_eq!(tape.len(), 8);
+        }
+
+        #[test]
+        fn increment() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.increment(0, 255);
+            assert_eq!(tape[0], 255);
+            tape.increment(0, 2);
+            assert_eq!(tape[0], 1);
+
+            let mut tape = self::tape(CellWidth::U16, TapeGrowth::Fixed);
+            tape.increment(0, 256);
+            assert_eq!(tape[0], 256);
+            tape.increment(0, 65_280);
+            assert_eq!(tape[0], 0);
+
+            let mut tape = self::tape(CellWidth::U32, TapeGrowth::Fixed);
+            tape.increment(0, 65_536);
+            assert_eq!(tape[0], 65_536);
+        }
+
+        #[test]
+        fn decrement() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.decrement(0, 1);
+            assert_eq!(tape[0], 255);
+
+            let mut tape = self::tape(CellWidth::U16, TapeGrowth::Fixed);
+            tape.decrement(0, 1);
+            assert_eq!(tape[0], 65_535);
+
+            let mut tape = self::tape(CellWidth::U32, TapeGrowth::Fixed);
+            tape.decrement(0, 1);
+            assert_eq!(tape[0], 4_294_967_295);
+        }
+
+        #[test]
+        fn increment_with_saturation() {
+            let mut tape = tape_with_arithmetic(ArithmeticPolicy::Saturate);
+            assert!(tape.increment(0, 250));
+            assert!(tape.increment(0, 10));
+            assert_eq!(tape[0], 255);
+        }
+
+        #[test]
+        fn increment_with_trap() {
+            let mut tape = tape_with_arithmetic(ArithmeticPolicy::Trap);
+            assert!(tape.increment(0, 255));
+            assert!(!tape.increment(0, 1));
+            assert_eq!(tape[0], 255);
+        }
+
+        #[test]
+        fn decrement_with_saturation() {
+            let mut tape = tape_with_arithmetic(ArithmeticPolicy::Saturate);
+            assert!(tape.increment(0, 5));
+            assert!(tape.decrement(0, 10));
+            assert_eq!(tape[0], 0);
+        }
+
+        #[test]
+        fn decrement_with_trap() {
+            let mut tape = tape_with_arithmetic(ArithmeticPolicy::Trap);
+            assert!(tape.increment(0, 5));
+            assert!(tape.decrement(0, 5));
+            assert!(!tape.decrement(0, 1));
+            assert_eq!(tape[0], 0);
+        }
+
+        #[test]
+        fn multiply_add() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.multiply_add(0, 10, 3);
+            assert_eq!(tape[0], 30);
+            tape.multiply_add(0, 10, -4);
+            assert_eq!(tape[0], 246);
+            tape.multiply_add(0, 200, 2);
+            assert_eq!(tape[0], 134);
+
+            let mut tape = self::tape(CellWidth::U32, TapeGrowth::Fixed);
+            tape.multiply_add(0, 4_000_000_000, 2);
+            assert_eq!(tape[0], 3_705_032_704);
+        }
+
+        #[test]
+        fn set() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.set(2, 0x1ff);
+            assert_eq!(tape.get(2), Some(0xff));
+            assert_eq!(tape.get(4), None);
+        }
+
+        #[test]
+        fn dump() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.set(1, 7);
+
+            let mut output = Vec::new();
+            tape.dump(1, &mut output).unwrap();
+            assert_eq!(output, b"head = 1, cells 0..=3 = 0 [7] 0 0\n");
+
+            let mut tape = Tape::new(&Configuration::default());
+            tape.set(20, 3);
+
+            let mut output = Vec::new();
+            tape.dump(20, &mut output).unwrap();
+            assert_eq!(
+                output,
+                &b"head = 20, cells 12..=28 = 0 0 0 0 0 0 0 0 [3] 0 0 0 0 0 0 0 0\n"[..],
+            );
+        }
+
+        #[test]
+        fn used_cells() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            assert_eq!(tape.used_cells(), &[0]);
+            tape.set(1, 7);
+            assert_eq!(tape.used_cells(), &[0]);
+            assert_eq!(tape.move_right(0, 2).unwrap(), 2);
+            assert_eq!(tape.used_cells(), &[0, 7, 0]);
+            assert_eq!(tape.move_left(2, 2).unwrap(), 0);
+            assert_eq!(tape.used_cells(), &[0, 7, 0]);
+        }
+
+        #[test]
+        fn used_cells_with_cleared_cells() {
+            // +>+>+[-]>
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.set(0, 1);
+            tape.move_right(0, 1).unwrap();
+            tape.set(1, 1);
+            tape.move_right(1, 1).unwrap();
+            tape.set(2, 1);
+            tape.set(2, 0);
+            tape.move_right(2, 1).unwrap();
+            assert_eq!(tape.used_cells(), &[1, 1, 0, 0]);
+            assert_eq!(tape.to_bytes(), &[1, 1, 0, 0]);
+        }
+
+        #[test]
+        fn used_cells_on_tape_growing_in_both_directions() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Both);
+            let mut head = 0;
+            tape.set(head, 1);
+            assert_eq!(tape.locate(&mut head, 5).unwrap(), 5);
+            assert_eq!(tape.locate(&mut head, -2).unwrap(), 0);
+            assert_eq!(tape.used_cells(), &[0, 0, 1, 0, 0, 0, 0, 0]);
+        }
+
+        #[test]
+        fn to_hex() {
+            let mut tape = Tape::new(&Configuration::default());
+            for (cell, &value) in b"Hello World!\n\0\0\0\xffz".iter().enumerate() {
+                tape.set(cell, u32::from(value));
+            }
+            tape.move_right(0, 17).unwrap();
+            assert_eq!(
+                tape.to_hex(),
+                "\
+00000000  48 65 6c 6c 6f 20 57 6f  72 6c 64 21 0a 00 00 00  |Hello World!....|
+00000010  ff 7a                                             |.z|
+",
+            );
+
+            let mut tape = self::tape(CellWidth::U16, TapeGrowth::Fixed);
+            tape.set(0, 0x41);
+            tape.set(2, 0x1234);
+            tape.move_right(0, 3).unwrap();
+            assert_eq!(
+                tape.to_hex(),
+                format!("00000000  0041 0000 1234 0000{}|A...|\n", " ".repeat(63)),
+            );
+
+            assert_eq!(
+                self::tape(CellWidth::U32, TapeGrowth::Fixed).to_hex(),
+                format!("00000000  00000000{}|.|\n", " ".repeat(138)),
+            );
+        }
+
+        #[test]
+        fn to_bytes() {
+            let mut tape = tape(CellWidth::U8, TapeGrowth::Fixed);
+            tape.set(0, 1);
+            tape.set(2, 0xff);
+            tape.move_right(0, 3).unwrap();
+            assert_eq!(tape.to_bytes(), &[1, 0, 0xff, 0]);
+
+            let mut tape = self::tape(CellWidth::U16, TapeGrowth::Fixed);
+            tape.set(1, 0x1234);
+            tape.move_right(0, 1).unwrap();
+            assert_eq!(tape.to_bytes(), &[0, 0, 0x34, 0x12]);
+
+            let mut tape = self::tape(CellWidth::U32, TapeGrowth::Fixed);
+            tape.set(0, 0x1234_5678);
+            assert_eq!(tape.to_bytes(), &[0x78, 0x56, 0x34, 0x12]);
+        }
+    }
+}
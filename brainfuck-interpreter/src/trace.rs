@@ -0,0 +1,563 @@
+use crate::{
+    bytecode::{
+        self,
+        Reader,
+    },
+    error::{
+        BytecodeError,
+        RuntimeError,
+        TraceError,
+    },
+    instruction::Instruction,
+    interpreter::Interpreter,
+    machine::Machine,
+    span::Location,
+    tape::Tape,
+};
+use std::{
+    convert::TryFrom,
+    io::{
+        Read,
+        Write,
+    },
+    str,
+};
+
+const MAGIC: &[u8; 4] = b"BFTR";
+const VERSION: u16 = 1;
+
+// A trace lists the location and the text of every instruction right after
+// its version, so it can be replayed without the script, and then records each
+// executed step as the index of the instruction, the head and the cells the
+// step wrote, each with its value before and after the step. Cells are
+// numbered from the first cell of the initial tape, so growing the tape to the
+// left doesn't renumber them. See the bytecode for the common layout of the
+// formats.
+impl Interpreter {
+    pub fn trace_with<R: Read, W: Write, T: Write>(
+        &self,
+        input: R,
+        mut output: W,
+        mut trace: T,
+    ) -> Result<Tape, RuntimeError> {
+        trace.write_all(&header(self))?;
+        self.with_input(input, |mut input| {
+            let mut machine = Machine::new(self);
+            let mut origin = 0;
+            let mut targets = Vec::new();
+            let mut record = Vec::new();
+            while !machine.is_finished() {
+                machine.check_limits()?;
+
+                let index = machine.instruction();
+                let instruction = &self.program()[index];
+                let head = machine.head();
+                let length = machine.tape().len();
+                written_cells(instruction, machine.tape(), head, &mut targets);
+
+                machine.step(&mut input, &mut output)?;
+
+                let cell = head as isize - origin as isize;
+                origin += match instruction {
+                    Instruction::MoveLeft(_) | Instruction::ScanLeft(_) => {
+                        machine.tape().len() - length
+                    },
+                    Instruction::MultiplyAdd(_) |
+                    Instruction::IncrementAt(_, _) |
+                    Instruction::DecrementAt(_, _) |
+                    Instruction::ReadAt(_) |
+                    Instruction::WriteAt(_) => machine.head() - head,
+                    _ => 0,
+                };
+
+                record.clear();
+                bytecode::write_unsigned(&mut record, index);
+                bytecode::write_signed(&mut record, cell);
+                bytecode::write_unsigned(&mut record, targets.len());
+                for &(offset, before) in &targets {
+                    let after = machine.tape()[(cell + offset + origin as isize) as usize];
+                    bytecode::write_signed(&mut record, cell + offset);
+                    bytecode::write_unsigned(&mut record, before as usize);
+                    bytecode::write_unsigned(&mut record, after as usize);
+                }
+                trace.write_all(&record)?;
+            }
+            output.flush()?;
+            trace.flush()?;
+
+            Ok(machine.into_tape())
+        })
+    }
+}
+
+fn header(interpreter: &Interpreter) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&VERSION.to_le_bytes());
+    bytecode::write_unsigned(&mut header, interpreter.program().len());
+    for (instruction, span) in interpreter.program().iter().zip(interpreter.spans()) {
+        let text = instruction.to_string();
+        bytecode::write_unsigned(&mut header, span.start.line);
+        bytecode::write_unsigned(&mut header, span.start.column);
+        bytecode::write_unsigned(&mut header, text.len());
+        header.extend_from_slice(text.as_bytes());
+    }
+    header
+}
+
+// Collects the offsets of the cells, which the instruction is about to write,
+// relative to the head along with their current values. Cells outside of the
+// tape are created with zero on demand.
+fn written_cells(
+    instruction: &Instruction,
+    tape: &Tape,
+    head: usize,
+    targets: &mut Vec<(isize, u32)>,
+) {
+    let value = |offset: isize| {
+        usize::try_from(head as isize + offset)
+            .ok()
+            .and_then(|cell| tape.get(cell))
+            .unwrap_or(0)
+    };
+
+    targets.clear();
+    match *instruction {
+        Instruction::Increment(_) |
+        Instruction::Decrement(_) |
+        Instruction::Read |
+        Instruction::SetZero => {
+            targets.push((0, value(0)));
+        },
+        Instruction::IncrementAt(offset, _) |
+        Instruction::DecrementAt(offset, _) |
+        Instruction::ReadAt(offset) => {
+            targets.push((offset, value(offset)));
+        },
+        Instruction::MultiplyAdd(ref multipliers) if value(0) != 0 => {
+            targets.extend(
+                multipliers
+                    .iter()
+                    .map(|&(offset, _)| (offset, value(offset))),
+            );
+            targets.push((0, value(0)));
+        },
+        _ => {},
+    }
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Trace {
+    locations: Vec<Location>,
+    instructions: Vec<String>,
+    steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    #[inline]
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+
+    #[inline]
+    pub fn location(&self, instruction: usize) -> Location {
+        self.locations[instruction]
+    }
+
+    #[inline]
+    pub fn instruction(&self, instruction: usize) -> &str {
+        &self.instructions[instruction]
+    }
+
+    // Returns the indices of the steps, which wrote the cell, along with the
+    // writes.
+    pub fn writes(&self, cell: isize) -> Vec<(usize, CellWrite)> {
+        self.steps
+            .iter()
+            .enumerate()
+            .flat_map(|(step, record)| {
+                let writes = record.writes.iter().filter(move |write| write.cell == cell);
+                writes.map(move |&write| (step, write))
+            })
+            .collect()
+    }
+
+    pub fn last_write(&self, cell: isize) -> Option<(usize, CellWrite)> {
+        self.steps
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(step, record)| {
+                record
+                    .writes
+                    .iter()
+                    .rev()
+                    .find(|write| write.cell == cell)
+                    .map(|&write| (step, write))
+            })
+    }
+}
+
+impl TryFrom<&[u8]> for Trace {
+    type Error = TraceError;
+
+    fn try_from(trace: &[u8]) -> Result<Trace, TraceError> {
+        if trace.len() < MAGIC.len() || &trace[..MAGIC.len()] != MAGIC {
+            return Err(TraceError::InvalidMagic);
+        }
+        if trace.len() < MAGIC.len() + 2 {
+            return Err(TraceError::Malformed);
+        }
+
+        let version = u16::from_le_bytes([trace[4], trace[5]]);
+        if version != VERSION {
+            return Err(TraceError::UnsupportedVersion(version));
+        }
+
+        let mut reader = Reader {
+            payload: trace,
+            position: MAGIC.len() + 2,
+        };
+        parse(&mut reader).map_err(|_| TraceError::Malformed)
+    }
+}
+
+fn parse(reader: &mut Reader) -> Result<Trace, BytecodeError> {
+    let length = reader.unsigned()?;
+    let mut locations = Vec::with_capacity(length.min(reader.payload.len()));
+    let mut instructions = Vec::with_capacity(length.min(reader.payload.len()));
+    for _ in 0..length {
+        locations.push(Location::new(reader.unsigned()?, reader.unsigned()?));
+        let length = reader.unsigned()?;
+        let text = str::from_utf8(reader.bytes(length)?).map_err(|_| BytecodeError::Malformed)?;
+        instructions.push(text.to_owned());
+    }
+
+    let mut steps = Vec::new();
+    while reader.position != reader.payload.len() {
+        let instruction = reader.unsigned()?;
+        if instruction >= instructions.len() {
+            return Err(BytecodeError::Malformed);
+        }
+        let head = reader.signed()?;
+        let length = reader.unsigned()?;
+        let mut writes = Vec::with_capacity(length.min(reader.payload.len()));
+        for _ in 0..length {
+            writes.push(CellWrite {
+                cell: reader.signed()?,
+                before: u32::try_from(reader.unsigned()?).map_err(|_| BytecodeError::Malformed)?,
+                after: u32::try_from(reader.unsigned()?).map_err(|_| BytecodeError::Malformed)?,
+            });
+        }
+        steps.push(TraceStep {
+            instruction,
+            head,
+            writes,
+        });
+    }
+
+    Ok(Trace {
+        locations,
+        instructions,
+        steps,
+    })
+}
+
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct TraceStep {
+    pub instruction: usize,
+    pub head: isize,
+    pub writes: Vec<CellWrite>,
+}
+
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CellWrite {
+    pub cell: isize,
+    pub before: u32,
+    pub after: u32,
+}
+
+#[cfg(test)]
+mod interpreter {
+    mod methods {
+        mod trace_with {
+            use crate::{
+                configuration::{
+                    Configuration,
+                    TapeGrowth,
+                },
+                error::RuntimeError,
+                interpreter::Interpreter,
+                trace::{
+                    CellWrite,
+                    Trace,
+                    TraceStep,
+                },
+            };
+            use std::convert::TryFrom;
+
+            fn trace(configuration: Configuration, script: &[u8], input: &[u8]) -> Trace {
+                let mut interpreter = Interpreter::with_configuration(configuration);
+                interpreter.load(script).unwrap();
+                let mut output = Vec::new();
+                let mut trace = Vec::new();
+                let tape = interpreter
+                    .trace_with(input, &mut output, &mut trace)
+                    .unwrap();
+                assert_eq!(
+                    tape,
+                    interpreter
+                        .execute_into_tape_with(input, Vec::new())
+                        .unwrap()
+                );
+                Trace::try_from(&trace[..]).unwrap()
+            }
+
+            fn step(instruction: usize, head: isize, writes: &[(isize, u32, u32)]) -> TraceStep {
+                TraceStep {
+                    instruction,
+                    head,
+                    writes: writes
+                        .iter()
+                        .map(|&(cell, before, after)| {
+                            CellWrite {
+                                cell,
+                                before,
+                                after,
+                            }
+                        })
+                        .collect(),
+                }
+            }
+
+            #[test]
+            fn steps() {
+                let trace = trace(Configuration::default(), b"++>,\n[-<+>]<.", b"\x02");
+                assert_eq!(
+                    trace.steps(),
+                    &[
+                        step(0, 0, &[(0, 0, 2)]),
+                        step(1, 0, &[]),
+                        step(2, 1, &[(1, 0, 2)]),
+                        step(3, 1, &[]),
+                        step(4, 1, &[(1, 2, 1)]),
+                        step(5, 1, &[]),
+                        step(6, 0, &[(0, 2, 3)]),
+                        step(7, 0, &[]),
+                        step(8, 1, &[]),
+                        step(4, 1, &[(1, 1, 0)]),
+                        step(5, 1, &[]),
+                        step(6, 0, &[(0, 3, 4)]),
+                        step(7, 0, &[]),
+                        step(8, 1, &[]),
+                        step(9, 1, &[]),
+                        step(10, 0, &[]),
+                    ]
+                );
+                assert_eq!(trace.instruction(1), ">");
+                assert_eq!(trace.location(3).to_string(), "2:1");
+                assert_eq!(trace.instruction(3), "[");
+            }
+
+            #[test]
+            fn optimized() {
+                let configuration = Configuration {
+                    optimize: true,
+                    ..Configuration::default()
+                };
+                let trace = trace(configuration, b"+++[->++>+<<]>[-]", b"");
+                assert_eq!(
+                    trace.steps(),
+                    &[
+                        step(0, 0, &[(0, 0, 3)]),
+                        step(1, 0, &[(1, 0, 6), (2, 0, 3), (0, 3, 0)]),
+                        step(2, 0, &[]),
+                        step(3, 1, &[(1, 6, 0)]),
+                    ]
+                );
+                assert_eq!(trace.instruction(1), "[->++>+<<]");
+            }
+
+            #[test]
+            fn tape_growing_to_the_left() {
+                let configuration = Configuration {
+                    tape_size: 2,
+                    tape_growth: TapeGrowth::Both,
+                    ..Configuration::default()
+                };
+                let steps = trace(configuration, b"+<<+>>>+<<<<<+", b"")
+                    .steps()
+                    .to_vec();
+                assert_eq!(
+                    steps,
+                    &[
+                        step(0, 0, &[(0, 0, 1)]),
+                        step(1, 0, &[]),
+                        step(2, -2, &[(-2, 0, 1)]),
+                        step(3, -2, &[]),
+                        step(4, 1, &[(1, 0, 1)]),
+                        step(5, 1, &[]),
+                        step(6, -4, &[(-4, 0, 1)]),
+                    ]
+                );
+
+                let configuration = Configuration {
+                    tape_size: 1,
+                    tape_growth: TapeGrowth::Both,
+                    optimize: true,
+                    ..Configuration::default()
+                };
+                let steps = trace(configuration, b"+[<+>-]<[<]+", b"").steps().to_vec();
+                assert_eq!(
+                    steps,
+                    &[
+                        step(0, 0, &[(0, 0, 1)]),
+                        step(1, 0, &[(-1, 0, 1), (0, 1, 0)]),
+                        step(2, 0, &[]),
+                        step(3, -1, &[]),
+                        step(4, -2, &[(-2, 0, 1)]),
+                    ]
+                );
+            }
+
+            #[test]
+            fn runtime_error() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load(b"+<").unwrap();
+                let mut trace = Vec::new();
+                match interpreter.trace_with(&b""[..], Vec::new(), &mut trace) {
+                    Err(error) => {
+                        match error.without_location() {
+                            RuntimeError::CellUnderflow => {},
+                            _ => unreachable!(),
+                        }
+                    },
+                    Ok(_) => unreachable!(),
+                }
+
+                let trace = Trace::try_from(&trace[..]).unwrap();
+                assert_eq!(trace.steps(), &[step(0, 0, &[(0, 0, 1)])]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod trace {
+    mod methods {
+        mod writes {
+            use crate::{
+                interpreter::Interpreter,
+                trace::{
+                    CellWrite,
+                    Trace,
+                },
+            };
+            use std::convert::TryFrom;
+
+            #[test]
+            fn writes() {
+                let mut interpreter = Interpreter::new();
+                interpreter.load(b"+>++<-->[-]").unwrap();
+                let mut trace = Vec::new();
+                interpreter
+                    .trace_with(&b""[..], Vec::new(), &mut trace)
+                    .unwrap();
+                let trace = Trace::try_from(&trace[..]).unwrap();
+
+                assert_eq!(
+                    trace.writes(0),
+                    vec![
+                        (
+                            0,
+                            CellWrite {
+                                cell: 0,
+                                before: 0,
+                                after: 1
+                            }
+                        ),
+                        (
+                            4,
+                            CellWrite {
+                                cell: 0,
+                                before: 1,
+                                after: 255
+                            }
+                        ),
+                    ]
+                );
+                assert_eq!(
+                    trace.last_write(0),
+                    Some((
+                        4,
+                        CellWrite {
+                            cell: 0,
+                            before: 1,
+                            after: 255,
+                        }
+                    ))
+                );
+                assert_eq!(
+                    trace.last_write(1),
+                    Some((
+                        9,
+                        CellWrite {
+                            cell: 1,
+                            before: 1,
+                            after: 0,
+                        }
+                    ))
+                );
+                assert_eq!(trace.writes(2), vec![]);
+                assert_eq!(trace.last_write(2), None);
+            }
+        }
+    }
+
+    mod traits {
+        mod try_from {
+            use crate::{
+                error::TraceError,
+                trace::Trace,
+            };
+            use std::convert::TryFrom;
+
+            #[test]
+            fn errors() {
+                for trace in &[&b""[..], b"BFT", b"BFSS\x01\x00"] {
+                    match Trace::try_from(*trace) {
+                        Err(TraceError::InvalidMagic) => {},
+                        _ => unreachable!(),
+                    }
+                }
+                match Trace::try_from(&b"BFTR\x02\x00"[..]) {
+                    Err(TraceError::UnsupportedVersion(2)) => {},
+                    _ => unreachable!(),
+                }
+
+                // A missing header, an unknown instruction, a truncated record and a huge
+                // value.
+                let traces: &[&[u8]] = &[
+                    b"BFTR\x01",
+                    b"BFTR\x01\x00",
+                    b"BFTR\x01\x00\x01\x01\x01\x01+\x01\x00\x00",
+                    b"BFTR\x01\x00\x01\x01\x01\x01+\x00\x00\x01\x00",
+                    b"BFTR\x01\x00\x01\x01\x01\x01+\x00\x00\x01\x00\x00\x80\x80\x80\x80\x10\x00",
+                ];
+                for trace in traces {
+                    match Trace::try_from(*trace) {
+                        Err(TraceError::Malformed) => {},
+                        _ => unreachable!(),
+                    }
+                }
+
+                let trace = b"BFTR\x01\x00\x01\x01\x01\x01+\x00\x00\x01\x00\x00\x01";
+                let trace = Trace::try_from(&trace[..]).unwrap();
+                assert_eq!(trace.steps().len(), 1);
+            }
+        }
+    }
+}